@@ -0,0 +1,105 @@
+use crate::model::{Task, TaskStatus};
+use crate::query::DateField;
+use chrono::{Days, NaiveDate};
+
+/// How far ahead "this week" and "starting soon" look
+const WEEK_DAYS: u64 = 7;
+
+/// Open tasks with dates that need attention relative to `today`
+#[derive(Debug, Clone, Default)]
+pub struct Agenda {
+    pub overdue: Vec<Task>,
+    pub today: Vec<Task>,
+    pub this_week: Vec<Task>,
+    pub starting_soon: Vec<Task>,
+}
+
+impl Agenda {
+    pub fn new(tasks: &[Task], today: NaiveDate) -> Agenda {
+        let week = today + Days::new(WEEK_DAYS);
+        let mut agenda = Agenda::default();
+        for task in tasks.iter().filter(|t| t.status != TaskStatus::Done) {
+            match DateField::Due.of(task) {
+                Some(due) if due < today => agenda.overdue.push(task.clone()),
+                Some(due) if due == today => agenda.today.push(task.clone()),
+                Some(due) if due <= week => agenda.this_week.push(task.clone()),
+                _ => (),
+            }
+            if let Some(start) = DateField::Start.of(task) {
+                if start >= today && start <= week {
+                    agenda.starting_soon.push(task.clone());
+                }
+            }
+        }
+        agenda.overdue.sort_by_key(|t| DateField::Due.of(t));
+        agenda.this_week.sort_by_key(|t| DateField::Due.of(t));
+        agenda.starting_soon.sort_by_key(|t| DateField::Start.of(t));
+        agenda
+    }
+
+    pub fn sections(&self) -> Vec<(&'static str, &Vec<Task>)> {
+        vec![
+            ("Overdue", &self.overdue),
+            ("Due today", &self.today),
+            ("Due this week", &self.this_week),
+            ("Starting soon", &self.starting_soon),
+        ]
+    }
+}
+
+/// The day a deferred task shows up in listings: the later of its visible and
+/// start dates
+pub fn appears_on(task: &Task) -> Option<NaiveDate> {
+    DateField::Visible
+        .of(task)
+        .into_iter()
+        .chain(DateField::Start.of(task))
+        .max()
+}
+
+/// Open tasks deferred until after `today`, in the order they will show up
+pub fn tickler(tasks: &[Task], today: NaiveDate) -> Vec<(NaiveDate, Task)> {
+    let mut deferred: Vec<(NaiveDate, Task)> = tasks
+        .iter()
+        .filter(|t| t.status != TaskStatus::Done)
+        .filter_map(|t| appears_on(t).map(|d| (d, t.clone())))
+        .filter(|(d, _)| *d > today)
+        .collect();
+    deferred.sort_by_key(|(d, _)| *d);
+    deferred
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(tasks: &[Task]) -> Vec<String> {
+        tasks.iter().map(|t| t.title()).collect()
+    }
+
+    #[test]
+    fn sorts_open_tasks_into_sections() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let tasks: Vec<Task> = [
+            "- late @d20240220",
+            "- later @d20240229",
+            "- now @d20240301",
+            "- next week @d20240308",
+            "- this week @d20240305",
+            "- far @d20240309",
+            "- done late @d20240220 @done",
+            "- starts @s20240303",
+            "- started @s20240229",
+            "- starts far @s20240309",
+        ]
+        .iter()
+        .map(|l| Task::from(l, "house.md"))
+        .collect();
+
+        let agenda = Agenda::new(&tasks, today);
+        assert_eq!(titles(&agenda.overdue), ["late", "later"]);
+        assert_eq!(titles(&agenda.today), ["now"]);
+        assert_eq!(titles(&agenda.this_week), ["this week", "next week"]);
+        assert_eq!(titles(&agenda.starting_soon), ["starts"]);
+    }
+}
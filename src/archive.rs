@@ -0,0 +1,94 @@
+use crate::crawl::modified_on;
+use crate::edit::{EditError, EditFile};
+use crate::journal;
+use crate::model::{Task, TaskDates, TaskStatus};
+use crate::outline::{insert_under_heading, item_block};
+use chrono::NaiveDate;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+/// Section of a project file that holds its archived tasks
+pub const ARCHIVE_HEADING: &str = "Archive";
+
+/// The day a done task was completed: its `@c` date, or else the last time its
+/// file was modified.
+pub fn completed_on(task: &Task) -> Option<NaiveDate> {
+    task.dates
+        .as_ref()
+        .and_then(|d| d.completed.as_deref())
+        .and_then(TaskDates::to_date)
+        .or_else(|| modified_on(&task.source.as_ref()?.path))
+}
+
+/// Done tasks completed more than `days` days before `today`
+pub fn is_archivable(task: &Task, days: i64, today: NaiveDate) -> bool {
+    task.status == TaskStatus::Done
+        && completed_on(task)
+            .map(|d| (today - d).num_days() > days)
+            .unwrap_or(false)
+}
+
+/// Move `tasks`, which must all come from the `project` file, out of it. They
+/// go under an archive section in the project itself or, when `archive_path`
+/// is given, under a heading linking back to the project in that file.
+pub fn archive_tasks(
+    project: &Path,
+    tasks: &[&Task],
+    archive_path: Option<&Path>,
+) -> Result<(), EditError> {
+    let mut file = EditFile::open(project)?;
+
+    let mut starts = tasks
+        .iter()
+        .map(|t| file.task_line(t))
+        .collect::<Result<Vec<usize>, EditError>>()?;
+    starts.sort();
+    let mut blocks: Vec<Range<usize>> = vec![];
+    for start in starts {
+        // Nested done tasks go along with their parent
+        if blocks.last().map(|b| b.contains(&start)).unwrap_or(false) {
+            continue;
+        }
+        blocks.push(item_block(&file.lines, start));
+    }
+
+    if blocks.is_empty() {
+        return Ok(());
+    }
+    let mut archived: Vec<Vec<String>> = blocks
+        .iter()
+        .rev()
+        .map(|block| file.lines.drain(block.clone()).collect())
+        .collect();
+    archived.reverse();
+    let command = format!(
+        "archive {} tasks from {}",
+        archived.len(),
+        project.display()
+    );
+
+    match archive_path {
+        Some(archive_path) => {
+            let stem = project
+                .file_stem()
+                .ok_or_else(|| {
+                    let e = io::Error::new(io::ErrorKind::InvalidInput, "not a file");
+                    EditError::Io(project.to_path_buf(), e)
+                })?
+                .to_string_lossy();
+            let heading = format!("[[{}]]", stem);
+            let mut archive = EditFile::open_or_create(archive_path)?;
+            for block in &archived {
+                insert_under_heading(&mut archive.lines, Some(&heading), block);
+            }
+            journal::commit(&command, &mut [&mut archive, &mut file])
+        }
+        None => {
+            for block in &archived {
+                insert_under_heading(&mut file.lines, Some(ARCHIVE_HEADING), block);
+            }
+            journal::commit(&command, &mut [&mut file])
+        }
+    }
+}
@@ -1,47 +1,350 @@
-use clap::Parser;
+use chrono::{Local, NaiveDate};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{ArgValueCandidates, ArgValueCompleter, CompleteEnv, CompletionCandidate};
 use colored::*;
-use gtd_cli::model::{ConfigFile, Project, Task, TaskDates, TaskStatus};
-use regex::Regex;
-use reqwest;
-use std::collections::HashMap;
-use std::fs;
-use std::sync::LazyLock;
-use walkdir::{DirEntry, WalkDir};
-
-static LIST_ITEM_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(\*|-)\s+").unwrap());
+use gtd_cli::agenda::{tickler, Agenda};
+use gtd_cli::archive::{archive_tasks, is_archivable};
+use gtd_cli::completion::{read_cache, save_cache, Names};
+use gtd_cli::crawl::{project_files, read_tasks, CrawlSettings};
+use gtd_cli::edit::write_atomic;
+use gtd_cli::find::{find, Field, Found};
+use gtd_cli::fuzzy;
+use gtd_cli::ical::{to_ics, Component};
+use gtd_cli::journal::{journal_path, read_journal, undo, JournalEntry};
+use gtd_cli::limits::WipLimits;
+use gtd_cli::listing::{group_tasks, sort_tasks, GroupBy, SortKey};
+use gtd_cli::model::{ConfigFile, ServerConfig, Task, TaskStatus};
+use gtd_cli::query::{parse_status, Cmp, DateField, DateValue, Query, QueryError};
+use gtd_cli::refile::move_task;
+use gtd_cli::render::{Column, Format};
+use gtd_cli::review::Review;
+use gtd_cli::snapshot::{self, read_snapshots, snapshots_path, Trends};
+use gtd_cli::stats::{Count, Stats};
+use gtd_cli::taskwarrior;
+use gtd_cli::todotxt;
+use gtd_cli::tui;
+use gtd_cli::update::{set_status, tag};
+use gtd_cli::view::View;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::exit;
 
 /// Turns a text-based knowledge base into a GTD system
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Config file, instead of $GTD_CONFIG, $XDG_CONFIG_HOME/gtd/config.json or ~/.gtd.json
+    #[arg(global = true, long, value_hint = clap::ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    /// Profile in the config to use, instead of $GTD_PROFILE
+    #[arg(global = true, long)]
+    profile: Option<String>,
+
     /// Root directory of the knowledge base
-    #[arg(short, long, value_hint = clap::ValueHint::DirPath)]
+    #[arg(global = true, short, long, value_hint = clap::ValueHint::DirPath)]
     dir: Option<std::path::PathBuf>,
 
     /// Task status todo, wip, or review
-    #[arg(short, long)]
+    #[arg(global = true, short, long, add = ArgValueCompleter::new(complete_statuses))]
     status: Option<String>,
 
     /// Not task status todo, wip, or review
-    #[arg(short = 'S', long)]
+    #[arg(global = true, short = 'S', long, add = ArgValueCompleter::new(complete_statuses))]
     not_status: Option<String>,
 
     /// Task context
-    #[arg(short, long)]
+    #[arg(global = true, short, long, add = ArgValueCompleter::new(complete_contexts))]
     context: Option<String>,
 
     /// Not Task context
-    #[arg(short = 'C', long)]
+    #[arg(global = true, short = 'C', long, add = ArgValueCompleter::new(complete_contexts))]
     not_context: Option<String>,
 
-    #[arg(short, long)]
-    pivot: Option<bool>,
+    /// Filter query, e.g. 'status:wip,review and (ctx:home or ctx:zz) and due<=+7d'
+    #[arg(global = true, short, long)]
+    query: Option<String>,
+
+    /// Only tasks due before this date, e.g. 2024-01-31, today or +7d
+    #[arg(global = true, long)]
+    due_before: Option<DateValue>,
+
+    /// Only tasks due after this date, e.g. 2024-01-31, today or -7d
+    #[arg(global = true, long)]
+    due_after: Option<DateValue>,
+
+    /// Include tasks with a start or visible date in the future
+    #[arg(global = true, long)]
+    include_future: bool,
+
+    /// Sort by due, start, priority, project, status or description, e.g. due,priority
+    #[arg(global = true, long, value_delimiter = ',')]
+    sort: Vec<SortKey>,
+
+    /// Group by project, context, status, due or person
+    #[arg(global = true, short, long)]
+    group_by: Option<GroupBy>,
+
+    /// List a view from the config
+    #[arg(global = true, short, long)]
+    view: Option<String>,
+
+    /// Print the listing as table, csv, md, html or ndjson
+    #[arg(global = true, long)]
+    format: Option<Format>,
+
+    /// Columns for --format: description, project, status, contexts, dates, path:line
+    #[arg(global = true, long, value_delimiter = ',')]
+    columns: Vec<Column>,
+
+    /// Fail when a WIP limit is exceeded
+    #[arg(global = true, long)]
+    strict: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the tasks, also what runs without a command
+    List {
+        /// Print the tasks as JSON, grouped when --group-by is given
+        #[arg(short, long)]
+        json: bool,
+    },
+    /// List the tasks per project and then per context
+    Pivot,
+    /// Push the tasks to the server in the config
+    Sync,
+    /// Move a task, with its nested items and notes, to another project file
+    Move {
+        /// Part of the task description, fuzzy matched
+        task: String,
+        /// Part of the project file name, fuzzy matched
+        #[arg(add = ArgValueCandidates::new(complete_projects))]
+        project: String,
+        /// Heading in the project file to add the task under
+        #[arg(long)]
+        heading: Option<String>,
+    },
+    /// Move done tasks out of their project files
+    Archive {
+        /// Only archive tasks completed more than this many days ago
+        #[arg(long, default_value_t = 7)]
+        days: i64,
+        /// Only show what would be archived
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Undo the last edits made by gtd commands
+    Undo {
+        /// Number of edits to undo
+        #[arg(short = 'n', long, default_value_t = 1)]
+        count: usize,
+    },
+    /// Show overdue tasks, tasks due today and this week, and tasks starting soon
+    Agenda,
+    /// Show deferred tasks in the order they will show up
+    Tickler,
+    /// Show the edits that can be undone, newest first
+    History {
+        /// Number of edits to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        count: usize,
+    },
+    /// Change the status of a task, within the WIP limits
+    SetStatus {
+        /// Part of the task description, fuzzy matched
+        task: String,
+        /// todo, wip, review, week, month, done or none
+        #[arg(id = "new_status", value_name = "STATUS", value_parser = parse_status)]
+        status: TaskStatus,
+        /// Change the status even if that exceeds a WIP limit
+        #[arg(long)]
+        force: bool,
+    },
+    /// Add contexts to a task, or remove them with a leading -, e.g.
+    /// `tag "call mom" phone -home`
+    Tag {
+        /// Part of the task description, fuzzy matched
+        task: String,
+        #[arg(required = true, allow_hyphen_values = true)]
+        contexts: Vec<String>,
+    },
+    /// Find tasks by fuzzy matching words against their description, project
+    /// and notes
+    Find {
+        #[arg(required = true)]
+        words: Vec<String>,
+        /// Number of tasks to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        count: usize,
+    },
+    /// Export tasks to other tools
+    Export {
+        #[command(subcommand)]
+        format: ExportFormat,
+    },
+    /// Add tasks from other tools to the inbox
+    Import {
+        #[command(subcommand)]
+        format: ImportFormat,
+    },
+    /// Inspect the config
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Show counts of tasks per status, context, project and due date
+    Stats {
+        /// Print the statistics as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show tasks created and completed per week, WIP over time and how long
+    /// tasks stay in @wip and @review, from the snapshots taken at every crawl
+    Trends {
+        /// Number of weeks to show
+        #[arg(short = 'n', long, default_value_t = 12)]
+        weeks: usize,
+        /// Print the trends as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Browse and update tasks in an interactive list
+    Tui,
+    /// List a view from the config
+    View {
+        /// Name of the view
+        name: String,
+    },
+    /// Report what needs attention in a weekly review
+    Review {
+        /// Print the report as Markdown
+        #[arg(long)]
+        markdown: bool,
+        /// Days after which an untouched @review task is stale
+        #[arg(long, default_value_t = 7)]
+        stale_days: i64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print every problem with the config, of all profiles unless one is
+    /// selected
+    Check,
+}
+
+#[derive(Subcommand, Debug)]
+enum ExportFormat {
+    /// iCalendar to-dos for the tasks with a due or start date
+    Ics {
+        /// Export all-day events instead of to-dos
+        #[arg(long)]
+        events: bool,
+        /// File to write to instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// A todo.txt file
+    Todotxt {
+        /// File to write to instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// JSON for `task import`
+    Taskwarrior {
+        /// File to write to instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ImportFormat {
+    /// A todo.txt file
+    Todotxt {
+        /// The file to import, - for stdin
+        file: PathBuf,
+    },
+    /// The JSON of `task export`
+    Taskwarrior {
+        /// The file to import, - for stdin
+        file: PathBuf,
+    },
+}
+
+/// The value of `--<name>` in `words`, as `--name value` or `--name=value`
+fn flag_value(words: &[String], name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    words
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, word)| match word.strip_prefix(&flag)? {
+            "" => words.get(i + 1).cloned(),
+            value => value.strip_prefix('=').map(String::from),
+        })
+}
+
+/// The names to complete, from the last crawl or else a crawl of the default
+/// directories of the config and profile on the command line being completed
+fn names() -> Names {
+    // The shell passes the command line being completed after `--`
+    let words: Vec<String> = std::env::args().skip_while(|a| a != "--").skip(1).collect();
+    let path = flag_value(&words, "config").map(PathBuf::from);
+    let profile = flag_value(&words, "profile");
+    let key = ConfigFile::state_key(path.as_deref(), profile.as_deref());
+    read_cache(key.as_deref()).unwrap_or_else(|| {
+        let Ok((config, _)) = ConfigFile::load(path.as_deref(), profile.as_deref()) else {
+            return Names::default();
+        };
+        let file_paths = project_files(
+            &config.default_dirs.clone().unwrap_or_default(),
+            &CrawlSettings::new(&config),
+        );
+        let tasks: Vec<Task> = file_paths.iter().flat_map(|p| read_tasks(p)).collect();
+        let names = Names::new(&tasks, &file_paths);
+        let _ = save_cache(&names, key.as_deref());
+        names
+    })
+}
+
+/// Candidates for the last name in a comma separated list, e.g. `home,wo`
+fn complete_list(current: &OsStr, names: &BTreeMap<String, usize>) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    let (before, last) = match current.rsplit_once(',') {
+        Some((before, last)) => (format!("{},", before), last),
+        None => (String::new(), current.as_ref()),
+    };
+    names
+        .iter()
+        .filter(|(name, _)| name.starts_with(last))
+        .map(|(name, count)| {
+            CompletionCandidate::new(format!("{}{}", before, name))
+                .help(Some(format!("{} tasks", count).into()))
+        })
+        .collect()
+}
+
+fn complete_contexts(current: &OsStr) -> Vec<CompletionCandidate> {
+    complete_list(current, &names().contexts)
+}
 
-    #[arg(short = 'j', long)]
-    json: Option<bool>,
+fn complete_statuses(current: &OsStr) -> Vec<CompletionCandidate> {
+    complete_list(current, &names().statuses)
+}
 
-    #[arg(short = 'w', long)]
-    web: Option<bool>,
+fn complete_projects() -> Vec<CompletionCandidate> {
+    names()
+        .projects
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
 }
 
 impl Args {
@@ -78,26 +381,70 @@ impl Args {
     pub fn not_context(&self) -> Vec<String> {
         Args::parse_context_arg(&self.not_context)
     }
-}
 
-fn is_hidden(entry: &DirEntry) -> bool {
-    entry
-        .file_name()
-        .to_str()
-        .map(|s| s.starts_with("."))
-        .unwrap_or(false)
+    pub fn view_name(&self) -> Option<&String> {
+        match &self.command {
+            Some(Command::View { name }) => Some(name),
+            _ => self.view.as_ref(),
+        }
+    }
+
+    /// The query ANDed with the view, status and context filters. Done tasks
+    /// are left out unless asked for or `include_done`, and so are deferred
+    /// tasks unless `include_future`.
+    pub fn filter(
+        &self,
+        view: Query,
+        default_not_context: Vec<String>,
+        include_future: bool,
+        include_done: bool,
+    ) -> Result<Query, QueryError> {
+        let query: Query = self.query.as_deref().unwrap_or("").parse()?;
+        let mut query = view.and(query);
+        let statuses = self.statuses();
+        if !statuses.is_empty() {
+            query = query.and(Query::Status(statuses));
+        }
+        let not_statuses = self.not_statuses();
+        if !not_statuses.is_empty() {
+            query = query.and(Query::Status(not_statuses).negate());
+        }
+        let contexts = self.contexts();
+        if !contexts.is_empty() {
+            query = query.and(Query::Context(contexts));
+        }
+        let not_contexts = self.not_context();
+        if !not_contexts.is_empty() {
+            query = query.and(Query::Context(not_contexts).negate());
+        }
+        if let Some(date) = self.due_before {
+            query = query.and(Query::Date(DateField::Due, Cmp::Lt, date));
+        }
+        if let Some(date) = self.due_after {
+            query = query.and(Query::Date(DateField::Due, Cmp::Gt, date));
+        }
+        if !default_not_context.is_empty() {
+            let hidden =
+                Query::Status(vec![TaskStatus::NoStatus]).and(Query::Context(default_not_context));
+            query = query.and(hidden.negate());
+        }
+        if !include_future {
+            query = query.and(Query::deferred().negate());
+        }
+        if !include_done && !query.mentions_status(TaskStatus::Done) {
+            query = query.and(Query::Status(vec![TaskStatus::Done]).negate());
+        }
+        Ok(query)
+    }
 }
 
-fn display_projects(projects: &Vec<Project>) {
-    for proj in projects {
-        let proj_line = format!("-- {} --", proj.file_name);
+fn display_projects(tasks: &[Task]) {
+    for (project, tasks) in group_tasks(tasks, GroupBy::Project) {
+        let proj_line = format!("-- {} --", project);
         println!("{}", proj_line.on_blue());
-        for status in TaskStatus::all() {
-            if !proj.tasks.contains_key(&status) {
-                continue;
-            }
-            println!("{}", status.to_color_str().dimmed());
-            for task in proj.tasks.get(&status).unwrap() {
+        for (_, tasks) in group_tasks(&tasks, GroupBy::Status) {
+            println!("{}", tasks[0].status.to_color_str().dimmed());
+            for task in tasks {
                 println!("{}", task)
             }
         }
@@ -105,216 +452,789 @@ fn display_projects(projects: &Vec<Project>) {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
-struct FlatContextTask {
-    context: String,
-    task: Task,
+fn print_tasks(tasks: &[Task]) {
+    for task in tasks {
+        println!("{}", task.ctx_line());
+    }
 }
 
-fn pivot_on_context(projects: &Vec<Project>) -> HashMap<String, Vec<FlatContextTask>> {
-    let flat_tasks: Vec<FlatContextTask> = projects
-        .iter()
-        .flat_map(|p| {
-            p.tasks.iter().flat_map(|t| {
-                t.1.iter().flat_map(|task| {
-                    task.contexts.iter().map(|c| FlatContextTask {
-                        context: c.into(),
-                        task: task.clone(),
-                    })
-                })
-            })
-        })
-        .collect();
-
-    flat_tasks.iter().fold(
-        HashMap::new(),
-        |mut map: HashMap<String, Vec<FlatContextTask>>, task| {
-            let mut value: Vec<FlatContextTask> =
-                map.get(&task.context).unwrap_or(&vec![]).to_vec();
-            value.push(task.clone());
-            map.insert(String::from(&task.context), value);
-            map
-        },
-    )
+/// The view named in the config, or exit listing the views there are
+fn find_view(views: &HashMap<String, View>, name: &str) -> View {
+    if let Some(view) = views.get(name) {
+        return view.clone();
+    }
+    let mut names: Vec<&String> = views.keys().collect();
+    names.sort();
+    eprintln!("No view named '{}'", name);
+    for name in names {
+        eprintln!("  {}", name);
+    }
+    exit(1)
 }
-fn flat_tasks(projects: &Vec<Project>) -> Vec<Task> {
-    projects
-        .iter()
-        .flat_map(|p| p.tasks.iter().flat_map(|t| t.1.clone()))
-        .collect()
+
+/// The filter, sorting and grouping of a view, or exit if it is invalid
+fn view_settings(view: &View) -> (Query, Vec<SortKey>, Option<GroupBy>) {
+    let settings = || Ok::<_, String>((view.filter()?, view.sort_keys()?, view.group_by()?));
+    settings().unwrap_or_else(|e| {
+        eprintln!("Invalid view: {}", e);
+        exit(1)
+    })
 }
 
-fn flat_tasks_dict(projects: &Vec<Project>) -> HashMap<String, Task> {
-    let tasks = &mut flat_tasks(projects);
-    tasks.sort_by(|a, b| a.description.cmp(&b.description));
+fn flat_tasks_dict(tasks: &[Task]) -> HashMap<String, Task> {
     tasks
         .iter()
         .map(|t| (t.description.clone(), t.clone()))
         .collect()
 }
 
-fn print_by_context(projects: &Vec<Project>) {
-    for (context, flat_tasks) in pivot_on_context(projects) {
-        let ctx_line = format!("-- {} --", context);
-        println!("{}", ctx_line.on_blue());
-        let mut sorted_flat_tasks = flat_tasks.clone();
-        sorted_flat_tasks.sort_by(|a, b| a.task.project.cmp(&b.task.project));
-        for t in sorted_flat_tasks {
-            println!("{}", t.task.ctx_line());
+fn sync_command(tasks: &[Task], server: Option<ServerConfig>) {
+    let Some(server) = server else {
+        eprintln!("No server in the config");
+        exit(1)
+    };
+    let tasks_string = serde_json::to_string(&flat_tasks_dict(tasks)).unwrap();
+    let res = reqwest::blocking::Client::new()
+        .post(server.host.clone() + "/tasks")
+        .body(tasks_string)
+        .header("Content-Type", "application/json")
+        .header("Authorization", "Basic ".to_owned() + &server.basic_token())
+        .send()
+        .and_then(|r| r.error_for_status());
+    match res {
+        Ok(_) => println!("Pushed {} tasks to {}", tasks.len(), server.host),
+        Err(e) => {
+            eprintln!("Could not sync: {}", e);
+            exit(1)
+        }
+    }
+}
+
+fn print_groups(tasks: &[Task], group_by: GroupBy) {
+    for (name, tasks) in group_tasks(tasks, group_by) {
+        let group_line = format!("-- {} --", name);
+        println!("{}", group_line.on_blue());
+        for task in tasks {
+            println!("{}", task.ctx_line());
         }
         println!()
     }
 }
 
-fn main() {
-    let config = ConfigFile::read();
-    let args = Args::parse();
-    let statuses = args.statuses();
-    let contexts = args.contexts();
-    let ignore_files = config.ignore_files.unwrap_or(vec![]);
-    let dirs = args
-        .dir
-        .map(|d| vec![d])
-        .unwrap_or(config.default_dirs.unwrap_or(vec![]));
-    println!("{:?}", dirs);
-    let default_not_context = match contexts.len() {
-        0 => config.default_not_context.unwrap_or(vec![]),
-        _ => vec![],
-    };
+#[derive(Serialize)]
+struct TaskGroup {
+    group: String,
+    tasks: Vec<Task>,
+}
+
+/// The single best fuzzy match, or exit listing the candidates if there is none
+fn pick_one<'a, T>(what: &str, pattern: &str, items: &'a [T], key: impl Fn(&T) -> String) -> &'a T {
+    let ranked = fuzzy::rank(pattern, items, &key);
+    match ranked.as_slice() {
+        [] => {
+            eprintln!("No {} matches '{}'", what, pattern);
+            exit(1)
+        }
+        [(_, item)] => item,
+        [(best, item), (second, _), ..] if best > second => item,
+        _ => {
+            eprintln!("'{}' matches more than one {}:", pattern, what);
+            for (_, item) in ranked.iter().take(10) {
+                eprintln!("  {}", key(item));
+            }
+            exit(1)
+        }
+    }
+}
+
+fn move_command(file_paths: &[PathBuf], task: &str, project: &str, heading: Option<&str>) {
+    let tasks: Vec<Task> = file_paths.iter().flat_map(|p| read_tasks(p)).collect();
+    let task = pick_one("task", task, &tasks, |t| {
+        format!("{} ({})", t.title(), t.project)
+    });
+    let target = pick_one("project", project, file_paths, |p| {
+        p.file_name().unwrap().to_string_lossy().into()
+    });
+    match move_task(task, target, heading) {
+        Ok(()) => println!("Moved '{}' to {}", task.title(), target.display()),
+        Err(e) => {
+            eprintln!("Could not move task: {}", e);
+            exit(1)
+        }
+    }
+}
+
+fn archive_command(file_paths: &[PathBuf], days: i64, dry_run: bool, archive_path: Option<&Path>) {
+    let today = Local::now().date_naive();
+    for file_path in file_paths {
+        let tasks = read_tasks(file_path);
+        let done: Vec<&Task> = tasks
+            .iter()
+            .filter(|t| is_archivable(t, days, today))
+            .collect();
+        if done.is_empty() {
+            continue;
+        }
+        println!("{}", format!("-- {} --", file_path.display()).on_blue());
+        for task in &done {
+            println!("{}", task);
+        }
+        if !dry_run {
+            if let Err(e) = archive_tasks(file_path, &done, archive_path) {
+                eprintln!("Could not archive {}: {}", file_path.display(), e);
+                exit(1)
+            }
+        }
+    }
+}
 
-    let file_paths = dirs.iter().flat_map(|dir| {
-        WalkDir::new(dir.as_path())
-            .into_iter()
-            .filter_entry(|e| !is_hidden(e))
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-            .filter(|e| !ignore_files.contains(&e.file_name().to_str().unwrap_or("").to_string()))
+fn set_status_command(
+    tasks: &[Task],
+    task: &str,
+    status: TaskStatus,
+    force: bool,
+    limits: &WipLimits,
+) {
+    let task = pick_one("task", task, tasks, |t| {
+        format!("{} ({})", t.title(), t.project)
     });
+    let exceeded = limits.exceeded_by(tasks, task, status);
+    if !exceeded.is_empty() && !force {
+        eprintln!("Not setting '{}' to {}:", task.title(), status);
+        for limit in exceeded {
+            eprintln!("  {}", limit);
+        }
+        eprintln!("Use --force to set it anyway");
+        exit(1)
+    }
+    match set_status(task, status, Local::now().date_naive()) {
+        Ok(()) => println!("Set '{}' to {}", task.title(), status),
+        Err(e) => {
+            eprintln!("Could not set status: {}", e);
+            exit(1)
+        }
+    }
+}
 
-    let re = Task::re_any();
+fn tag_command(tasks: &[Task], task: &str, contexts: &[String]) {
+    let task = pick_one("task", task, tasks, |t| {
+        format!("{} ({})", t.title(), t.project)
+    });
+    let (remove, add): (Vec<&str>, Vec<&str>) = contexts
+        .iter()
+        .map(|c| c.trim_start_matches("#x"))
+        .partition(|c| c.starts_with('-'));
+    let remove: Vec<&str> = remove.iter().map(|c| &c[1..]).collect();
+    match tag(task, &add, &remove) {
+        Ok(()) => println!("Tagged '{}'", task.title()),
+        Err(e) => {
+            eprintln!("Could not tag: {}", e);
+            exit(1)
+        }
+    }
+}
 
-    let projects: Vec<Project> = file_paths
-        .flat_map(|file_path| {
-            let file_name: String = file_path.file_name().to_str().unwrap().into();
+/// Warn about the WIP limits that are exceeded, and fail in strict mode
+fn check_limits(limits: &WipLimits, tasks: &[Task], strict: bool) {
+    let exceeded = limits.exceeded(tasks);
+    for limit in &exceeded {
+        eprintln!("{}", format!(" WIP limit exceeded: {} ", limit).on_red());
+    }
+    if strict && !exceeded.is_empty() {
+        exit(1)
+    }
+}
 
-            let file_content = fs::read_to_string(&file_path.path()).unwrap_or("".to_string());
+fn print_agenda(agenda: &Agenda) {
+    for (title, tasks) in agenda.sections() {
+        if tasks.is_empty() {
+            continue;
+        }
+        println!("{}", format!("-- {} --", title).on_blue());
+        for task in tasks {
+            let dates = task
+                .dates
+                .as_ref()
+                .map(|d| d.markers().join(" "))
+                .unwrap_or_default();
+            println!("{} {}", task.ctx_line(), dates.dimmed());
+        }
+        println!()
+    }
+}
 
-            let task_lines = file_content
-                .lines()
-                .map(|line| String::from(line))
-                .filter(|line| LIST_ITEM_RE.is_match(line));
+fn print_tickler(deferred: &[(NaiveDate, Task)]) {
+    for (date, task) in deferred {
+        println!("{} {}", date.to_string().dimmed(), task.ctx_line());
+    }
+}
 
-            let first_line = task_lines.clone().next().unwrap_or("".into());
-            let gtd_task = if first_line.starts_with("- @gtd") {
-                Some(Task::from(&first_line, &file_name))
-            } else {
-                None
-            };
+fn print_review(review: &Review) {
+    if let Some(count) = review.inbox_count {
+        println!("{}", "-- Inbox --".on_blue());
+        println!("{} items to process\n", count);
+    }
+    println!("{}", "-- Projects without a next action --".on_blue());
+    for project in &review.no_next_action {
+        println!("{}", project);
+    }
+    println!();
+    for (title, tasks) in review.task_sections() {
+        println!("{}", format!("-- {} --", title).on_blue());
+        for task in tasks {
+            println!("{}", task.ctx_line());
+        }
+        println!()
+    }
+}
+
+fn highlight(text: &str, positions: &[usize]) -> String {
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| match positions.contains(&i) {
+            true => c.to_string().yellow().bold().to_string(),
+            false => c.to_string(),
+        })
+        .collect()
+}
+
+fn print_found(found: &[Found]) {
+    for Found { task, hits, .. } in found {
+        let hit_in = |field: Field| hits.iter().find(|h| h.field == field);
+        let title = match hit_in(Field::Description) {
+            Some(hit) => highlight(&hit.text, &hit.positions),
+            None => task.title(),
+        };
+        let project = match hit_in(Field::Project) {
+            Some(hit) => highlight(&hit.text, &hit.positions),
+            None => task.project.clone(),
+        };
+        let source = task.source.as_ref().map(|s| s.to_string());
+        println!("{} {}", project.bold(), title);
+        for hit in hits.iter().filter(|h| h.field == Field::Notes) {
+            println!("    {}", highlight(&hit.text, &hit.positions));
+        }
+        println!("    {}", source.unwrap_or_default().dimmed());
+    }
+}
 
-            let tasks: Vec<Task> = if gtd_task.is_some() {
-                let gt = gtd_task.as_ref().unwrap().clone();
-                task_lines
-                    .filter(|l| !l.starts_with("- @gtd"))
-                    .map(|l| {
-                        let mut t = Task::from(&l, &file_name);
-                        if t.status == TaskStatus::NoStatus {
-                            // Replace NoStatus with GTD task status
-                            t.status = gt.status.clone();
-                        }
-
-                        let start = t.dates.as_ref().map(|d| d.start.clone()).flatten().or(gt
-                            .dates
-                            .as_ref()
-                            .map(|d| d.start.clone())
-                            .flatten());
-                        let due = t.dates.as_ref().map(|d| d.due.clone()).flatten().or(gt
-                            .dates
-                            .as_ref()
-                            .map(|d| d.due.clone())
-                            .flatten());
-                        let visible = t.dates.as_ref().map(|d| d.due.clone()).flatten().or(gt
-                            .dates
-                            .as_ref()
-                            .map(|d| d.visible.clone())
-                            .flatten());
-                        t.dates = match (start, due, visible) {
-                            (None, None, None) => None,
-                            (s, d, v) => Some(TaskDates {
-                                start: s,
-                                due: d,
-                                visible: v,
-                            }),
-                        };
-                        t.contexts.append(gt.contexts.clone().as_mut());
-                        t
-                    })
-                    .collect::<Vec<Task>>()
-            } else {
-                task_lines
-                    .filter(|line| re.is_match(line))
-                    .map(|l| Task::from(&l, &file_name))
-                    .filter(|task| !task.has_noflags())
-                    .filter(|task| statuses.is_empty() || statuses.contains(&task.status))
-                    .filter(|task| {
-                        contexts.is_empty() || task.contexts.iter().any(|c| contexts.contains(c))
-                    })
-                    .filter(|task| {
-                        !(task
-                            .contexts
-                            .iter()
-                            .any(|c| default_not_context.contains(c))
-                            & task.status.eq(&TaskStatus::NoStatus))
-                    })
-                    .collect::<Vec<Task>>()
+fn export_command(tasks: &[Task], format: &ExportFormat) {
+    let (content, output) = match format {
+        ExportFormat::Ics { events, output } => {
+            let kind = match events {
+                true => Component::Event,
+                false => Component::Todo,
             };
+            (to_ics(tasks, kind), output)
+        }
+        ExportFormat::Todotxt { output } => (todotxt::export(tasks), output),
+        ExportFormat::Taskwarrior { output } => (taskwarrior::export(tasks), output),
+    };
+    match output {
+        Some(path) => write_atomic(path, &content).unwrap_or_else(|e| {
+            eprintln!("Could not export: {}", e);
+            exit(1)
+        }),
+        None => print!("{}", content),
+    }
+}
+
+fn import_command(format: &ImportFormat, inbox_path: Option<String>, file_paths: &[PathBuf]) {
+    let Some(inbox_path) = inbox_path else {
+        eprintln!("Importing needs an inbox_path in the config");
+        exit(1)
+    };
+    let inbox = Path::new(&inbox_path);
+    let (ImportFormat::Todotxt { file } | ImportFormat::Taskwarrior { file }) = format;
+    let content = match file.to_str() {
+        Some("-") => std::io::read_to_string(std::io::stdin()),
+        _ => std::fs::read_to_string(file),
+    };
+    let imported = content
+        .map_err(|e| e.to_string())
+        .and_then(|c| match format {
+            ImportFormat::Todotxt { .. } => {
+                todotxt::import(&c, inbox, file_paths).map_err(|e| e.to_string())
+            }
+            ImportFormat::Taskwarrior { .. } => taskwarrior::parse(&c)
+                .map_err(|e| e.to_string())
+                .and_then(|tasks| {
+                    taskwarrior::import(&tasks, inbox, file_paths).map_err(|e| e.to_string())
+                }),
+        });
+    match imported {
+        Ok(counts) if counts.is_empty() => println!("No tasks in {}", file.display()),
+        Ok(counts) => {
+            for (path, count) in counts {
+                println!("Added {} tasks to {}", count, path.display());
+            }
+        }
+        Err(e) => {
+            eprintln!("Could not import {}: {}", file.display(), e);
+            exit(1)
+        }
+    }
+}
+
+fn share(count: usize, total: usize) -> f64 {
+    match total {
+        0 => 0.0,
+        _ => 100.0 * count as f64 / total as f64,
+    }
+}
+
+fn print_counts(title: &str, counts: &[Count], total: usize) {
+    println!("{}", format!("-- {} --", title).on_blue());
+    for Count { name, count } in counts {
+        println!("{:>5} {:>5.1}%  {}", count, share(*count, total), name);
+    }
+    println!()
+}
+
+fn print_stats(stats: &Stats) {
+    println!("{} tasks\n", stats.total);
+    print_counts("Status", &stats.by_status, stats.total);
+    print_counts("Context", &stats.by_context, stats.total);
+    print_counts("Project", &stats.by_project, stats.total);
+    let missing = [
+        Count {
+            name: "no context".into(),
+            count: stats.no_context,
+        },
+        Count {
+            name: "no status".into(),
+            count: stats.no_status,
+        },
+    ];
+    print_counts("Missing", &missing, stats.total);
+    let open = stats.due.iter().map(|c| c.count).sum();
+    print_counts("Due", &stats.due, open);
+    println!("{}", "-- Oldest open items --".on_blue());
+    for aged in &stats.oldest {
+        println!("{} {}", aged.since.dimmed(), aged.task.ctx_line());
+    }
+    println!();
+    print_counts("Largest projects", &stats.largest_projects, open);
+}
+
+fn print_trends(trends: &Trends) {
+    println!("{}", "-- Per week --".on_blue());
+    println!(
+        "{:<10} {:>7} {:>9} {:>4} {:>6}",
+        "week", "created", "completed", "wip", "review"
+    );
+    for week in &trends.weeks {
+        println!(
+            "{:<10} {:>7} {:>9} {:>4} {:>6}",
+            week.start, week.created, week.completed, week.wip, week.review
+        );
+    }
+    println!();
+    println!("{}", "-- Days in status --".on_blue());
+    for dwell in &trends.dwell {
+        println!(
+            "{:<8} {:>4} tasks, mean {:.1}, median {:.1}, longest {:.1}",
+            dwell.status.to_string(),
+            dwell.tasks,
+            dwell.mean_days,
+            dwell.median_days,
+            dwell.max_days
+        );
+    }
+}
+
+fn print_journal_entry(i: usize, entry: &JournalEntry) {
+    println!("{:>3} {} {}", i, entry.time.dimmed(), entry.command);
+    for change in &entry.files {
+        println!("      {}", change.path.display().to_string().dimmed());
+    }
+}
+
+fn history_command(count: usize) {
+    let path = journal_path().unwrap_or_else(|e| {
+        eprintln!("Could not read the journal: {}", e);
+        exit(1)
+    });
+    let entries = read_journal(&path);
+    for (i, entry) in entries.iter().rev().take(count).enumerate() {
+        print_journal_entry(i + 1, entry);
+    }
+}
+
+fn undo_command(count: usize) {
+    match undo(count) {
+        Ok(undone) if undone.is_empty() => println!("Nothing to undo"),
+        Ok(undone) => {
+            for (i, entry) in undone.iter().enumerate() {
+                print_journal_entry(i + 1, entry);
+            }
+        }
+        Err(e) => {
+            eprintln!("Could not undo: {}", e);
+            exit(1)
+        }
+    }
+}
+
+fn config_check_command(path: Option<&Path>, profile: Option<&str>) {
+    let errors = ConfigFile::check(path, profile);
+    if errors.is_empty() {
+        if let Ok(path) = ConfigFile::path(path) {
+            println!("{} has no problems", path.display());
+        }
+        return;
+    }
+    for error in &errors {
+        println!("{}", error);
+    }
+    exit(1)
+}
+
+/// The knowledge base of the config, with the files that are crawled
+struct Kb {
+    config: ConfigFile,
+    /// Key of the snapshots and completions of this config and profile
+    state_key: Option<String>,
+    file_paths: Vec<PathBuf>,
+}
+
+impl Kb {
+    fn load(args: &Args) -> Kb {
+        let (path, profile) = (args.config.as_deref(), args.profile.as_deref());
+        let (config, warnings) = ConfigFile::load(path, profile).unwrap_or_else(|e| {
+            eprintln!("Invalid config: {}", e);
+            eprintln!("Run `gtd-cli config check` to see all problems");
+            exit(1)
+        });
+        for warning in &warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        let dirs = args
+            .dir
+            .clone()
+            .map(|d| vec![d])
+            .unwrap_or(config.default_dirs.clone().unwrap_or(vec![]));
+        Kb {
+            state_key: ConfigFile::state_key(path, profile),
+            file_paths: project_files(&dirs, &CrawlSettings::new(&config)),
+            config,
+        }
+    }
+
+    fn tasks(&self) -> Vec<Task> {
+        self.file_paths.iter().flat_map(|p| read_tasks(p)).collect()
+    }
 
-            if tasks.is_empty() {
-                return None;
+    fn limits(&self) -> WipLimits {
+        self.config.wip_limits.clone().unwrap_or_default()
+    }
+
+    /// All tasks, checked against the WIP limits. A crawl of the configured
+    /// directories also writes the calendar, a snapshot and the completions.
+    fn crawl(&self, args: &Args) -> Vec<Task> {
+        let tasks = self.tasks();
+        check_limits(&self.limits(), &tasks, args.strict);
+        // A crawl of another directory would look like most tasks disappeared
+        if args.dir.is_some() {
+            return tasks;
+        }
+        if let Some(ics_path) = &self.config.ics_path {
+            let kind = match self.config.ics_events.unwrap_or(false) {
+                true => Component::Event,
+                false => Component::Todo,
+            };
+            if let Err(e) = write_atomic(Path::new(ics_path), &to_ics(&tasks, kind)) {
+                eprintln!("Could not write {}: {}", ics_path, e);
             }
+        }
+        let key = self.state_key.as_deref();
+        if let Err(e) = snapshot::append(&tasks, key) {
+            eprintln!("Could not save a snapshot: {}", e);
+        }
+        if let Err(e) = save_cache(&Names::new(&tasks, &self.file_paths), key) {
+            eprintln!("Could not save the completions: {}", e);
+        }
+        tasks
+    }
+}
+
+/// Which tasks a command shows and how, from the options and the view
+struct Selection {
+    filter: Query,
+    sort: Vec<SortKey>,
+    group_by: Option<GroupBy>,
+}
 
-            let grouped_tasks = tasks.iter().fold(
-                HashMap::new(),
-                |mut map: HashMap<TaskStatus, Vec<Task>>, task| {
-                    let mut value: Vec<Task> = map.get(&task.status).unwrap_or(&vec![]).to_vec();
-                    value.push(task.clone());
-                    map.insert(task.status, value);
-                    map
-                },
+impl Selection {
+    fn new(args: &Args, config: &ConfigFile) -> Selection {
+        let view = args
+            .view_name()
+            .map(|name| find_view(&config.views.clone().unwrap_or_default(), name));
+        let (view_filter, view_sort, view_group_by) =
+            view_settings(&view.clone().unwrap_or_default());
+        let view_contexts = view.as_ref().and_then(|v| v.context.as_ref());
+        // Statistics are over the whole knowledge base unless filtered explicitly
+        let stats = matches!(args.command, Some(Command::Stats { .. }));
+        let default_not_context = match (args.contexts().len(), view_contexts, stats) {
+            (0, None, false) => config.default_not_context.clone().unwrap_or(vec![]),
+            _ => vec![],
+        };
+        let include_future = args.include_future
+            || matches!(
+                args.command,
+                Some(
+                    Command::Agenda
+                        | Command::Tickler
+                        | Command::Review { .. }
+                        | Command::Find { .. }
+                        | Command::Export { .. }
+                        | Command::Stats { .. }
+                )
             );
-            return Some(Project {
-                file_name: file_path.file_name().to_str().unwrap().into(),
-                tasks: grouped_tasks,
+        let filter = args
+            .filter(view_filter, default_not_context, include_future, stats)
+            .unwrap_or_else(|e| {
+                eprintln!("Invalid query: {}", e);
+                exit(1)
             });
-        })
+        Selection {
+            filter,
+            sort: match args.sort.is_empty() {
+                true => view_sort,
+                false => args.sort.clone(),
+            },
+            group_by: args.group_by.or(view_group_by),
+        }
+    }
+}
+
+/// Crawl the knowledge base and keep the tasks the options and view select
+fn select(args: &Args, today: NaiveDate) -> (Kb, Vec<Task>, Selection) {
+    let kb = Kb::load(args);
+    let selection = Selection::new(args, &kb.config);
+    let tasks = kb
+        .crawl(args)
+        .into_iter()
+        .filter(|task| selection.filter.matches(task, today))
         .collect();
+    (kb, tasks, selection)
+}
 
-    if args.pivot.unwrap_or(false) {
-        display_projects(&projects);
-        println!("---------------------------------------------------------");
-        print_by_context(&projects);
-    } else if args.json.unwrap_or(false) {
-        print!(
-            "{}",
-            serde_json::to_string_pretty(&flat_tasks(&projects)).unwrap()
-        );
+fn main() {
+    CompleteEnv::with_factory(Args::command).complete();
+    let args = Args::parse();
+    let listing = matches!(
+        args.command,
+        None | Some(Command::List { .. } | Command::View { .. })
+    );
+    if !listing && (args.format.is_some() || !args.columns.is_empty()) {
+        eprintln!("--format and --columns only apply to list and view");
+        exit(1)
+    }
+    let today = Local::now().date_naive();
+
+    match &args.command {
+        Some(Command::Config {
+            command: ConfigCommand::Check,
+        }) => config_check_command(args.config.as_deref(), args.profile.as_deref()),
+        Some(Command::Undo { count }) => undo_command(*count),
+        Some(Command::History { count }) => history_command(*count),
+        Some(Command::Move {
+            task,
+            project,
+            heading,
+        }) => move_command(
+            &Kb::load(&args).file_paths,
+            task,
+            project,
+            heading.as_deref(),
+        ),
+        Some(Command::Import { format }) => {
+            let kb = Kb::load(&args);
+            import_command(format, kb.config.inbox_path, &kb.file_paths);
+        }
+        Some(Command::Archive { days, dry_run }) => {
+            let kb = Kb::load(&args);
+            let archive_path = kb.config.archive_path.map(PathBuf::from);
+            archive_command(&kb.file_paths, *days, *dry_run, archive_path.as_deref());
+        }
+        Some(Command::SetStatus {
+            task,
+            status,
+            force,
+        }) => {
+            let kb = Kb::load(&args);
+            set_status_command(&kb.tasks(), task, *status, *force, &kb.limits());
+        }
+        Some(Command::Tag { task, contexts }) => {
+            tag_command(&Kb::load(&args).tasks(), task, contexts)
+        }
+        Some(Command::Trends { weeks, json }) => {
+            let kb = Kb::load(&args);
+            kb.crawl(&args);
+            let path = snapshots_path(kb.state_key.as_deref()).unwrap_or_else(|e| {
+                eprintln!("Could not read the snapshots: {}", e);
+                exit(1)
+            });
+            let snapshots = read_snapshots(&path);
+            let trends = Trends::new(&snapshots, *weeks);
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&trends).unwrap()),
+                false => print_trends(&trends),
+            }
+        }
+        Some(Command::Tui) => {
+            let kb = Kb::load(&args);
+            kb.crawl(&args);
+            let selection = Selection::new(&args, &kb.config);
+            let limits = kb.limits();
+            if let Err(e) = tui::run(kb.file_paths, selection.filter, limits, kb.config.server) {
+                eprintln!("Could not run the TUI: {}", e);
+                exit(1)
+            }
+        }
+        Some(Command::Sync) => {
+            let (kb, tasks, _) = select(&args, today);
+            sync_command(&tasks, kb.config.server);
+        }
+        Some(Command::Agenda) => print_agenda(&Agenda::new(&select(&args, today).1, today)),
+        Some(Command::Tickler) => print_tickler(&tickler(&select(&args, today).1, today)),
+        Some(Command::Export { format }) => export_command(&select(&args, today).1, format),
+        Some(Command::Stats { json }) => {
+            let stats = Stats::new(&select(&args, today).1, today);
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&stats).unwrap()),
+                false => print_stats(&stats),
+            }
+        }
+        Some(Command::Find { words, count }) => {
+            let found = find(words, &select(&args, today).1);
+            print_found(&found[..(*count).min(found.len())]);
+        }
+        Some(Command::Review {
+            markdown,
+            stale_days,
+        }) => {
+            let (kb, tasks, _) = select(&args, today);
+            let inbox_path = kb.config.inbox_path.map(PathBuf::from);
+            let review = Review::new(
+                &tasks,
+                &kb.file_paths,
+                inbox_path.as_deref(),
+                *stale_days,
+                today,
+            );
+            match markdown {
+                true => print!("{}", review.to_markdown()),
+                false => print_review(&review),
+            }
+        }
+        Some(Command::Pivot) => {
+            let (_, mut tasks, selection) = select(&args, today);
+            sort_tasks(&mut tasks, &selection.sort);
+            display_projects(&tasks);
+            println!("---------------------------------------------------------");
+            print_groups(&tasks, GroupBy::Context);
+        }
+        None | Some(Command::View { .. }) => {
+            let (_, mut tasks, selection) = select(&args, today);
+            sort_tasks(&mut tasks, &selection.sort);
+            list_command(
+                &tasks,
+                &args.format,
+                &args.columns,
+                false,
+                selection.group_by,
+            );
+        }
+        Some(Command::List { json }) => {
+            let (_, mut tasks, selection) = select(&args, today);
+            sort_tasks(&mut tasks, &selection.sort);
+            list_command(
+                &tasks,
+                &args.format,
+                &args.columns,
+                *json,
+                selection.group_by,
+            );
+        }
+    }
+}
+
+fn list_command(
+    tasks: &[Task],
+    format: &Option<Format>,
+    columns: &[Column],
+    json: bool,
+    group_by: Option<GroupBy>,
+) {
+    if let Some(format) = format {
+        let columns = match columns.is_empty() {
+            true => Column::defaults(),
+            false => columns.to_vec(),
+        };
+        print!("{}", format.renderer().render(tasks, &columns));
+    } else if json {
+        let json = match group_by {
+            Some(group_by) => serde_json::to_string_pretty(
+                &group_tasks(tasks, group_by)
+                    .into_iter()
+                    .map(|(group, tasks)| TaskGroup { group, tasks })
+                    .collect::<Vec<_>>(),
+            ),
+            None => serde_json::to_string_pretty(tasks),
+        };
+        print!("{}", json.unwrap());
+    } else if let Some(group_by) = group_by {
+        print_groups(tasks, group_by);
     } else {
-        //display_projects(&projects);
-    }
-    if args.web.unwrap_or(true) && config.server.is_some() {
-        let tasks_string = serde_json::to_string(&flat_tasks_dict(&projects)).unwrap();
-        let server_cnf = config.server.unwrap().clone();
-        let url = server_cnf.host.clone() + "/tasks";
-        let client = reqwest::blocking::Client::new();
-        let res = client
-            .post(url)
-            .body(tasks_string)
-            .header("Content-Type", "application/json")
-            .header(
-                "Authorization",
-                "Basic ".to_owned() + &server_cnf.basic_token(),
-            )
-            .send()
-            .unwrap();
-        println!("{:?}", res.text());
+        print_tasks(tasks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn completion_reads_config_and_profile_from_the_line() {
+        let line = words("gtd-cli --config /tmp/gtd.json --profile=work -c ho");
+        assert_eq!(flag_value(&line, "config"), Some("/tmp/gtd.json".into()));
+        assert_eq!(flag_value(&line, "profile"), Some("work".into()));
+        assert_eq!(flag_value(&words("gtd-cli -c ho"), "profile"), None);
+    }
+
+    #[test]
+    fn args_are_consistent() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn tag_takes_contexts_to_remove() {
+        let args = Args::try_parse_from(words("gtd-cli tag mom phone -home")).unwrap();
+        match args.command {
+            Some(Command::Tag { task, contexts }) => {
+                assert_eq!(task, "mom");
+                assert_eq!(contexts, ["phone", "-home"]);
+            }
+            command => panic!("parsed {:?}", command),
+        }
+    }
+
+    #[test]
+    fn set_status_takes_a_status() {
+        let args = Args::try_parse_from(words("gtd-cli -s wip set-status x done")).unwrap();
+        assert_eq!(args.status.as_deref(), Some("wip"));
+        match args.command {
+            Some(Command::SetStatus { task, status, .. }) => {
+                assert_eq!(task, "x");
+                assert_eq!(status, TaskStatus::Done);
+            }
+            command => panic!("parsed {:?}", command),
+        }
     }
 }
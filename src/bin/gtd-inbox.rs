@@ -1,38 +1,316 @@
-use chrono::Local;
+use chrono::{Local, NaiveDate};
 use clap::{Arg, Command};
-use gtd_cli::model::ConfigFile;
-use std::fs::OpenOptions;
-use std::io::Write;
+use gtd_cli::crawl::{project_files, CrawlSettings};
+use gtd_cli::edit::{EditError, EditFile};
+use gtd_cli::fuzzy;
+use gtd_cli::journal;
+use gtd_cli::model::{ConfigFile, TaskStatus};
+use gtd_cli::outline::{indent_of, insert_under_heading, is_list_item, item_block};
+use gtd_cli::refile::same_file;
+use gtd_cli::update::{self, with_due, with_start, with_status};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+fn or_exit<T>(result: Result<T, EditError>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1)
+    })
+}
+
+fn inbox_path(config: &ConfigFile) -> PathBuf {
+    config
+        .inbox_path
+        .clone()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            eprintln!("No inbox_path in the config");
+            exit(1)
+        })
+}
+
+fn prompt(question: &str) -> Option<String> {
+    print!("{} ", question);
+    io::stdout().flush().ok()?;
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim().to_string()),
+    }
+}
+
+fn prompt_date(question: &str) -> Option<NaiveDate> {
+    loop {
+        let answer = prompt(question)?;
+        if answer.is_empty() {
+            return None;
+        }
+        let date = NaiveDate::parse_from_str(&answer, "%Y%m%d")
+            .or(NaiveDate::parse_from_str(&answer, "%Y-%m-%d"));
+        match date {
+            Ok(d) => return Some(d),
+            Err(_) => println!("Expected a date like 20240131 or 2024-01-31"),
+        }
+    }
+}
+
+fn prompt_status() -> Option<TaskStatus> {
+    let status = prompt("Status (todo, wip, review, week, month, done):")?;
+    match TaskStatus::classify(&format!("@{}", status)) {
+        TaskStatus::NoStatus => {
+            println!("Unknown status {}", status);
+            None
+        }
+        s => Some(s),
+    }
+}
+
+/// The item line with the comma separated contexts added as markers
+fn with_contexts(line: &str, contexts: &str) -> String {
+    let contexts: Vec<&str> = contexts
+        .split(',')
+        .map(|c| c.trim())
+        .filter(|c| !c.is_empty())
+        .collect();
+    update::with_contexts(line, &contexts, &[])
+}
+
+/// The projects an inbox item can be refiled to, which is never the inbox
+fn refile_targets(projects: Vec<PathBuf>, inbox_path: &Path) -> Vec<PathBuf> {
+    projects
+        .into_iter()
+        .filter(|p| !same_file(p, inbox_path))
+        .collect()
+}
+
+fn choose_project(projects: &[PathBuf]) -> Option<PathBuf> {
+    loop {
+        let filter = prompt("Project (fuzzy filter on name):")?;
+        let matches: Vec<&PathBuf> = fuzzy::rank(&filter, projects, |p| {
+            p.file_name().unwrap().to_string_lossy().into()
+        })
+        .into_iter()
+        .map(|(_, p)| p)
+        .take(20)
+        .collect();
+        match matches.len() {
+            0 => println!("No project matches {}", filter),
+            1 => return Some(matches[0].clone()),
+            _ => {
+                for (i, p) in matches.iter().enumerate() {
+                    println!("{:>3}: {}", i + 1, p.display());
+                }
+                let pick = prompt("Number (empty to filter again):")?;
+                if let Some(p) = pick
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| matches.get(i.wrapping_sub(1)))
+                {
+                    return Some((*p).clone());
+                }
+            }
+        }
+    }
+}
+
+fn process(config: &ConfigFile) {
+    let inbox_path = inbox_path(config);
+    let projects = refile_targets(
+        project_files(
+            &config.default_dirs.clone().unwrap_or_default(),
+            &CrawlSettings::new(config),
+        ),
+        &inbox_path,
+    );
+    let mut inbox = or_exit(EditFile::open(&inbox_path));
+    let today = Local::now().date_naive();
+
+    let mut i = 0;
+    while i < inbox.lines.len() {
+        let lines = &mut inbox.lines;
+        if !is_list_item(&lines[i]) || indent_of(&lines[i]) > 0 {
+            i += 1;
+            continue;
+        }
+        let block = item_block(lines, i);
+        let item = lines[i].trim().to_string();
+        println!();
+        for line in &lines[block.clone()] {
+            println!("{}", line);
+        }
+        let Some(action) = prompt(
+            "[d]elete, do it [n]ow, set s[t]atus, [c]ontexts or [w]hen, [r]efile, [s]kip, [q]uit:",
+        ) else {
+            break;
+        };
+        let command = match action.as_str() {
+            "d" => {
+                lines.drain(block);
+                format!("delete '{}' from the inbox", item)
+            }
+            "n" => {
+                if prompt("Do it now, then press enter to clear it from the inbox").is_none() {
+                    break;
+                }
+                lines.drain(block);
+                format!("do '{}' from the inbox", item)
+            }
+            "t" => {
+                let Some(status) = prompt_status() else {
+                    continue;
+                };
+                lines[i] = with_status(&lines[i], status, today);
+                format!("set '{}' to {} in the inbox", item, status)
+            }
+            "c" => {
+                let contexts = prompt("Contexts (comma separated):").unwrap_or_default();
+                lines[i] = with_contexts(&lines[i], &contexts);
+                format!("set the contexts of '{}' in the inbox", item)
+            }
+            "w" => {
+                if let Some(start) = prompt_date("Start date:") {
+                    lines[i] = with_start(&lines[i], start);
+                }
+                if let Some(due) = prompt_date("Due date:") {
+                    lines[i] = with_due(&lines[i], due);
+                }
+                format!("set the dates of '{}' in the inbox", item)
+            }
+            "r" => {
+                let Some(project) = choose_project(&projects) else {
+                    break;
+                };
+                let refiled: Vec<String> = lines.drain(block).collect();
+                let mut project_file = or_exit(EditFile::open(&project));
+                insert_under_heading(&mut project_file.lines, None, &refiled);
+                let command = format!("refile '{}' to {}", item, project.display());
+                or_exit(journal::commit(
+                    &command,
+                    &mut [&mut project_file, &mut inbox],
+                ));
+                println!("Refiled to {}", project.display());
+                continue;
+            }
+            "s" => {
+                i = block.end;
+                continue;
+            }
+            "q" => break,
+            _ => continue,
+        };
+        or_exit(journal::commit(&command, &mut [&mut inbox]));
+    }
+}
+
+fn capture(config: &ConfigFile, message: String) {
+    let inbox_path = inbox_path(config);
+    let mut inbox = or_exit(EditFile::open_or_create(&inbox_path));
+
+    let today = Local::now().naive_local().date().format("%Y-%m-%d");
+    let inbox_line = format!("- {} @d{}", message, today);
+    println!("{}", inbox_line);
+
+    inbox.lines.push(inbox_line.clone());
+    or_exit(journal::commit(
+        &format!("capture '{}'", inbox_line),
+        &mut [&mut inbox],
+    ));
+}
+
+fn words(matches: &clap::ArgMatches, id: &str) -> Vec<String> {
+    matches
+        .get_many::<String>(id)
+        .map(|m| m.cloned().collect())
+        .unwrap_or_default()
+}
 
 fn main() {
-    let config = ConfigFile::read();
     let matches = Command::new("inbox")
+        .about("Capture a message with `gtd-inbox <message>` or process the inbox")
+        .subcommand_required(true)
+        .allow_external_subcommands(true)
+        .external_subcommand_value_parser(clap::value_parser!(String))
         .arg(
-            Arg::new("message")
-                .help("The message to process")
-                .required(true)
-                .num_args(1..),
+            Arg::new("config")
+                .long("config")
+                .global(true)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Config file, instead of $GTD_CONFIG, $XDG_CONFIG_HOME/gtd/config.json or ~/.gtd.json"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .global(true)
+                .help("Profile in the config to use, instead of $GTD_PROFILE"),
+        )
+        .subcommand(
+            Command::new("process")
+                .about("Clarify and organize the inbox one item at a time")
+                // `gtd-inbox process taxes` is a message, not a subcommand
+                .arg(Arg::new("message").num_args(1..).trailing_var_arg(true).hide(true)),
+        )
+        .subcommand(
+            Command::new("capture")
+                .about("Capture the message, also if it starts with a subcommand")
+                .arg(
+                    Arg::new("message")
+                        .help("The message to capture")
+                        .required(true)
+                        .num_args(1..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true),
+                ),
         )
         .get_matches();
+    let (config, warnings) = ConfigFile::load(
+        matches.get_one::<PathBuf>("config").map(PathBuf::as_path),
+        matches.get_one::<String>("profile").map(String::as_str),
+    )
+    .unwrap_or_else(|e| {
+        eprintln!("Invalid config: {}", e);
+        exit(1)
+    });
+    for warning in &warnings {
+        eprintln!("Warning: {}", warning);
+    }
 
-    let message: String = matches
-        .get_many::<String>("message")
-        .unwrap()
-        .cloned()
-        .collect::<Vec<_>>()
-        .join(" ");
+    let message = match matches.subcommand() {
+        Some(("process", m)) if m.get_many::<String>("message").is_none() => {
+            return process(&config);
+        }
+        Some(("process", m)) => [vec!["process".to_string()], words(m, "message")].concat(),
+        Some(("capture", m)) => words(m, "message"),
+        Some((first, m)) => [vec![first.to_string()], words(m, "")].concat(),
+        None => unreachable!("a subcommand is required"),
+    };
+    capture(&config, message.join(" "))
+}
 
-    let inbox_path = config.inbox_path.expect("inbox_path must exist in config");
-    let mut inbox_file = OpenOptions::new()
-        .append(true)
-        .open(inbox_path)
-        .expect("cannot open file");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
 
-    let today = Local::now().naive_local().date().format("%Y-%m-%d");
-    let inbox_line = format!("\n- {} @d{}", message, today);
-    println!("{}", inbox_line);
+    #[test]
+    fn contexts_are_added_as_markers() {
+        assert_eq!(
+            with_contexts("- call mom ", "phone, home,"),
+            "- call mom #xphone #xhome"
+        );
+        assert_eq!(with_contexts("- call mom", ""), "- call mom");
+    }
+
+    #[test]
+    fn the_inbox_is_not_a_refile_target() {
+        let dir = std::env::temp_dir().join(format!("gtd-inbox-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (inbox, house) = (dir.join("inbox.md"), dir.join("house.md"));
+        fs::write(&inbox, "- paint door\n").unwrap();
+        fs::write(&house, "").unwrap();
+        let targets = refile_targets(vec![inbox.clone(), house.clone()], &dir.join("./inbox.md"));
+        fs::remove_dir_all(&dir).unwrap();
 
-    inbox_file
-        .write(inbox_line.as_bytes())
-        .expect("write failed");
+        assert_eq!(targets, vec![house]);
+    }
 }
@@ -1,6 +1,6 @@
 use axum::{
     extract::ws::{Message, WebSocket, WebSocketUpgrade},
-    extract::State,
+    extract::{Query as QueryParams, State},
     http::StatusCode,
     response::IntoResponse,
     routing::get,
@@ -12,9 +12,15 @@ use tokio::sync::watch::{self, Sender};
 use tower_http::cors::CorsLayer;
 
 use std::collections::HashMap;
+use std::process::exit;
 use std::sync::{Arc, RwLock};
 
-use gtd_cli::model::Task;
+use chrono::Local;
+use gtd_cli::listing::sort_tasks;
+use gtd_cli::model::{ConfigFile, Task};
+use gtd_cli::query::Query;
+use gtd_cli::view::View;
+use serde::Deserialize;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
@@ -66,11 +72,55 @@ fn add_starred(tasks: HashMap<String, Task>, starred_descriptions: Vec<String>)
     sorted_tasks
 }
 
-async fn get_tasks(State(state): State<SharedState>) -> Result<impl IntoResponse, StatusCode> {
+#[derive(Deserialize)]
+struct TasksParams {
+    /// Filter query, see [gtd_cli::query]
+    q: Option<String>,
+    /// Name of a view from the config
+    view: Option<String>,
+    /// Include tasks with a start or visible date in the future
+    #[serde(default)]
+    include_future: bool,
+}
+
+async fn get_tasks(
+    State(state): State<SharedState>,
+    QueryParams(params): QueryParams<TasksParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
     tracing::info!("get_tasks");
+    let bad_request = |e| (StatusCode::BAD_REQUEST, e);
+    let query: Query = params
+        .q
+        .as_deref()
+        .unwrap_or("")
+        .parse()
+        .map_err(|e| bad_request(format!("Invalid query: {}", e)))?;
+    let today = Local::now().date_naive();
     let s = state.read().unwrap();
-    let tasks = add_starred(s.tasks.clone(), s.starred_descriptions.clone());
-    Ok(Json(tasks.clone()))
+    let view = match &params.view {
+        Some(name) => s
+            .views
+            .get(name)
+            .cloned()
+            .ok_or_else(|| bad_request(format!("No view named '{}'", name)))?,
+        None => View::default(),
+    };
+    let mut query = view.filter().map_err(bad_request)?.and(query);
+    if !params.include_future {
+        query = query.and(Query::deferred().negate());
+    }
+    let sort = view.sort_keys().map_err(bad_request)?;
+    let mut tasks: Vec<Task> = add_starred(s.tasks.clone(), s.starred_descriptions.clone())
+        .into_iter()
+        .filter(|t| query.matches(t, today))
+        .collect();
+    sort_tasks(&mut tasks, &sort);
+    Ok(Json(tasks))
+}
+
+async fn get_views(State(state): State<SharedState>) -> Json<HashMap<String, View>> {
+    tracing::info!("get_views");
+    Json(state.read().unwrap().views.clone())
 }
 
 type SharedState = Arc<RwLock<AppState>>;
@@ -78,6 +128,7 @@ type SharedState = Arc<RwLock<AppState>>;
 struct AppState {
     tasks: HashMap<String, Task>,
     starred_descriptions: Vec<String>,
+    views: HashMap<String, View>,
     tx: Sender<String>,
 }
 
@@ -89,11 +140,21 @@ async fn main() {
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
+    // The server can run on a machine without a config, it only takes the views
+    let views = match ConfigFile::load(None, None) {
+        Ok((config, _)) => config.views.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("Invalid config: {}", e);
+            exit(1)
+        }
+    };
+
     let (tx, mut rx) = watch::channel("hello".to_string());
 
     let shared_state = Arc::new(RwLock::new(AppState {
         tasks: HashMap::new(),
         starred_descriptions: vec![],
+        views,
         tx,
     }));
 
@@ -119,6 +180,7 @@ async fn main() {
     let app = Router::new()
         .route("/", get(index))
         .route("/tasks", get(get_tasks).post(set_tasks))
+        .route("/views", get(get_views))
         .route("/star", post(star_task))
         .route("/ws", get(ws_handler))
         .layer(CorsLayer::permissive())
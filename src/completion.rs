@@ -0,0 +1,75 @@
+use crate::config::ConfigError;
+use crate::edit::{write_atomic, EditError};
+use crate::model::{data_dir, Task, TaskStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// The names the shell completes, with how many tasks use each
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Names {
+    /// Contexts without the `#x`
+    pub contexts: BTreeMap<String, usize>,
+    /// Statuses without the `@`
+    pub statuses: BTreeMap<String, usize>,
+    /// Project file names
+    pub projects: Vec<String>,
+}
+
+impl Names {
+    pub fn new(tasks: &[Task], file_paths: &[PathBuf]) -> Names {
+        let mut names = Names::default();
+        for status in TaskStatus::all() {
+            if status != TaskStatus::NoStatus {
+                names.statuses.insert(status_name(status), 0);
+            }
+        }
+        for task in tasks {
+            for context in &task.contexts {
+                let context = context.trim_start_matches("#x").to_string();
+                *names.contexts.entry(context).or_default() += 1;
+            }
+            if task.status != TaskStatus::NoStatus {
+                *names.statuses.entry(status_name(task.status)).or_default() += 1;
+            }
+        }
+        names.projects = file_paths
+            .iter()
+            .filter_map(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .collect();
+        names.projects.sort();
+        names.projects.dedup();
+        names
+    }
+}
+
+fn status_name(status: TaskStatus) -> String {
+    status.to_string().trim_start_matches('@').to_string()
+}
+
+/// Each knowledge base keeps its own names, see [ConfigFile::state_key]
+///
+/// [ConfigFile::state_key]: crate::model::ConfigFile::state_key
+pub fn cache_path(key: Option<&str>) -> Result<PathBuf, ConfigError> {
+    Ok(match key {
+        Some(key) => data_dir()?.join(format!("completions-{}.json", key)),
+        None => data_dir()?.join("completions.json"),
+    })
+}
+
+/// The names from the last crawl, if there was one
+pub fn read_cache(key: Option<&str>) -> Option<Names> {
+    serde_json::from_str(&fs::read_to_string(cache_path(key).ok()?).ok()?).ok()
+}
+
+/// Save the names for the next completion, unless they did not change
+pub fn save_cache(names: &Names, key: Option<&str>) -> Result<(), EditError> {
+    if read_cache(key).as_ref() == Some(names) {
+        return Ok(());
+    }
+    let path = cache_path(key).map_err(EditError::NoDataDir)?;
+    fs::create_dir_all(path.parent().unwrap()).map_err(|e| EditError::Io(path.clone(), e))?;
+    write_atomic(&path, &serde_json::to_string(names).unwrap())
+}
@@ -0,0 +1,524 @@
+use crate::crawl::glob;
+use crate::model::{ConfigFile, ServerConfig};
+use reqwest::Url;
+use std::env::var;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// A problem with the config that stops it from being used
+#[derive(Debug)]
+pub enum ConfigError {
+    /// `$HOME` is needed to find the config
+    NoHome,
+    /// None of the places the config is looked for has one
+    NotFound(Vec<PathBuf>),
+    Read(PathBuf, io::Error),
+    Json {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    UnknownKey {
+        path: PathBuf,
+        key: String,
+    },
+    NoProfile(String),
+    MissingDir {
+        key: String,
+        dir: PathBuf,
+    },
+    NotWritable {
+        key: String,
+        path: PathBuf,
+        reason: String,
+    },
+    BadHost {
+        key: String,
+        host: String,
+        reason: String,
+    },
+    BadGlob {
+        key: String,
+        glob: String,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoHome => write!(f, "$HOME is not defined"),
+            Self::NotFound(paths) => {
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "no config file, looked for {}", paths.join(" and "))
+            }
+            Self::Read(path, e) => write!(f, "{}: {}", path.display(), e),
+            Self::Json {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            Self::UnknownKey { path, key } => {
+                write!(f, "{}: unknown key '{}'", path.display(), key)
+            }
+            Self::NoProfile(name) => write!(f, "no profile named '{}'", name),
+            Self::MissingDir { key, dir } => {
+                write!(f, "{}: {} is not a directory", key, dir.display())
+            }
+            Self::NotWritable { key, path, reason } => {
+                write!(f, "{}: {} is not writable, {}", key, path.display(), reason)
+            }
+            Self::BadHost { key, host, reason } => {
+                write!(f, "{}: '{}' is not a server URL, {}", key, host, reason)
+            }
+            Self::BadGlob { key, glob, reason } => {
+                write!(f, "{}: '{}' is not a glob, {}", key, glob, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+pub(crate) fn home() -> Result<PathBuf, ConfigError> {
+    var("HOME")
+        .ok()
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+        .ok_or(ConfigError::NoHome)
+}
+
+/// `$XDG_CONFIG_HOME/gtd/config.json` or `~/.gtd.json`, whichever exists
+fn default_path() -> Result<PathBuf, ConfigError> {
+    let home = home()?;
+    let config_home = var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".config"));
+    let paths = vec![config_home.join("gtd/config.json"), home.join(".gtd.json")];
+    match paths.iter().find(|p| p.exists()) {
+        Some(path) => Ok(path.clone()),
+        None => Err(ConfigError::NotFound(paths)),
+    }
+}
+
+/// The config as read, with the keys it has that gtd does not know
+fn parse(path: &Path) -> Result<(ConfigFile, Vec<String>), ConfigError> {
+    let content = fs::read_to_string(path).map_err(|e| ConfigError::Read(path.into(), e))?;
+    let mut unknown = vec![];
+    let mut deserializer = serde_json::Deserializer::from_str(&content);
+    // Keys in an `Option` come with a `?` in their path
+    let mut ignored = |key: serde_ignored::Path| {
+        let key = key.to_string();
+        let parts: Vec<&str> = key.split('.').filter(|p| *p != "?").collect();
+        unknown.push(parts.join("."));
+    };
+    let config = serde_ignored::deserialize(&mut deserializer, &mut ignored)
+        .and_then(|config| deserializer.end().map(|_| config))
+        .map_err(|e| {
+            let message = e.to_string();
+            ConfigError::Json {
+                path: path.into(),
+                line: e.line(),
+                column: e.column(),
+                message: match message.rsplit_once(" at line ") {
+                    Some((message, _)) => message.to_string(),
+                    None => message,
+                },
+            }
+        })?;
+    Ok((config, unknown))
+}
+
+/// Whether the permissions let gtd write the file, and its directory where
+/// [write_atomic] replaces it
+///
+/// [write_atomic]: crate::edit::write_atomic
+fn writable(path: &Path) -> Result<(), String> {
+    let dir = path
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    if !dir.is_dir() {
+        return Err(format!("{} does not exist", dir.display()));
+    }
+    for path in [dir, path] {
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.permissions().readonly() => {
+                return Err(format!("{} is read-only", path.display()))
+            }
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.to_string()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn check_host(server: &ServerConfig) -> Result<(), String> {
+    let url = Url::parse(&server.host).map_err(|e| e.to_string())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("it must start with http:// or https://".into());
+    }
+    if url.host_str().is_none() {
+        return Err("it has no host".into());
+    }
+    Ok(())
+}
+
+/// The problems with the settings in use, from the profile where it sets them
+/// and else from the top level
+fn problems(config: &ConfigFile, profile: Option<(&str, &ConfigFile)>) -> Vec<ConfigError> {
+    let pick = |name: &str, own: Option<bool>| match (profile, own) {
+        (Some((profile, _)), Some(true)) => format!("profiles.{}.{}", profile, name),
+        _ => name.to_string(),
+    };
+    let profile = profile.map(|(_, p)| p);
+    let mut problems = vec![];
+
+    let default_dirs = profile.and_then(|p| p.default_dirs.as_ref());
+    let key = pick("default_dirs", profile.map(|_| default_dirs.is_some()));
+    for dir in default_dirs
+        .or(config.default_dirs.as_ref())
+        .into_iter()
+        .flatten()
+    {
+        if !dir.is_dir() {
+            problems.push(ConfigError::MissingDir {
+                key: key.clone(),
+                dir: dir.clone(),
+            });
+        }
+    }
+
+    let inbox_path = profile.and_then(|p| p.inbox_path.as_ref());
+    let key = pick("inbox_path", profile.map(|_| inbox_path.is_some()));
+    if let Some(path) = inbox_path.or(config.inbox_path.as_ref()) {
+        if let Err(reason) = writable(Path::new(path)) {
+            problems.push(ConfigError::NotWritable {
+                key,
+                path: path.into(),
+                reason,
+            });
+        }
+    }
+
+    let globs = [
+        (
+            "include_globs",
+            profile.and_then(|p| p.include_globs.as_ref()),
+        ),
+        (
+            "exclude_globs",
+            profile.and_then(|p| p.exclude_globs.as_ref()),
+        ),
+    ];
+    for (name, globs) in globs {
+        let key = pick(name, profile.map(|_| globs.is_some()));
+        let top = match name {
+            "include_globs" => config.include_globs.as_ref(),
+            _ => config.exclude_globs.as_ref(),
+        };
+        for g in globs.or(top).into_iter().flatten() {
+            if let Err(e) = glob(g) {
+                problems.push(ConfigError::BadGlob {
+                    key: key.clone(),
+                    glob: g.clone(),
+                    reason: e.kind().to_string(),
+                });
+            }
+        }
+    }
+
+    let server = profile.and_then(|p| p.server.as_ref());
+    let key = pick("server.host", profile.map(|_| server.is_some()));
+    if let Some(server) = server.or(config.server.as_ref()) {
+        if let Err(reason) = check_host(server) {
+            problems.push(ConfigError::BadHost {
+                key,
+                host: server.host.clone(),
+                reason,
+            });
+        }
+    }
+    problems
+}
+
+impl ConfigFile {
+    fn new() -> ConfigFile {
+        ConfigFile {
+            default_dirs: None,
+            inbox_path: None,
+            ignore_files: None,
+            include_globs: None,
+            exclude_globs: None,
+            max_depth: None,
+            gitignore: None,
+            default_not_context: None,
+            server: None,
+            archive_path: None,
+            wip_limits: None,
+            views: None,
+            ics_path: None,
+            ics_events: None,
+            profiles: None,
+        }
+    }
+
+    /// Where the config is: `path` when given, else `$GTD_CONFIG`, else
+    /// `$XDG_CONFIG_HOME/gtd/config.json` or `~/.gtd.json`, whichever exists
+    pub fn path(path: Option<&Path>) -> Result<PathBuf, ConfigError> {
+        if let Some(path) = path {
+            return Ok(path.to_path_buf());
+        }
+        if let Some(path) = var("GTD_CONFIG").ok().filter(|p| !p.is_empty()) {
+            return Ok(PathBuf::from(path));
+        }
+        default_path()
+    }
+
+    /// What keeps the snapshots and completions of one knowledge base apart
+    /// from those of another: the profile and, for a config other than the
+    /// default one, a hash of its path. `None` for the default config.
+    pub fn state_key(path: Option<&Path>, profile: Option<&str>) -> Option<String> {
+        let canonical = |p: &Path| fs::canonicalize(p).unwrap_or(p.to_path_buf());
+        let config = match (ConfigFile::path(path), default_path()) {
+            (Ok(path), Ok(default)) if canonical(&path) == canonical(&default) => None,
+            (Ok(path), _) => {
+                let path = canonical(&path).to_string_lossy().to_string();
+                let hash = Uuid::new_v5(&Uuid::NAMESPACE_URL, path.as_bytes()).simple();
+                Some(hash.to_string()[..8].to_string())
+            }
+            (Err(_), _) => None,
+        };
+        match (config, ConfigFile::profile_name(profile)) {
+            (Some(config), Some(profile)) => Some(format!("{}-{}", config, profile)),
+            (config, profile) => config.or(profile),
+        }
+    }
+
+    /// The profile to use: `profile` when given, else `$GTD_PROFILE`
+    pub fn profile_name(profile: Option<&str>) -> Option<String> {
+        profile
+            .map(String::from)
+            .or_else(|| var("GTD_PROFILE").ok().filter(|p| !p.is_empty()))
+    }
+
+    /// The profile settings in place of the top-level ones they set
+    fn with_profile(self, profile: ConfigFile) -> ConfigFile {
+        ConfigFile {
+            default_dirs: profile.default_dirs.or(self.default_dirs),
+            inbox_path: profile.inbox_path.or(self.inbox_path),
+            ignore_files: profile.ignore_files.or(self.ignore_files),
+            include_globs: profile.include_globs.or(self.include_globs),
+            exclude_globs: profile.exclude_globs.or(self.exclude_globs),
+            max_depth: profile.max_depth.or(self.max_depth),
+            gitignore: profile.gitignore.or(self.gitignore),
+            default_not_context: profile.default_not_context.or(self.default_not_context),
+            server: profile.server.or(self.server),
+            archive_path: profile.archive_path.or(self.archive_path),
+            wip_limits: profile.wip_limits.or(self.wip_limits),
+            views: profile.views.or(self.views),
+            ics_path: profile.ics_path.or(self.ics_path),
+            ics_events: profile.ics_events.or(self.ics_events),
+            profiles: None,
+        }
+    }
+
+    /// The profile named `name` in the config
+    fn profile(&self, name: &str) -> Result<&ConfigFile, ConfigError> {
+        self.profiles
+            .as_ref()
+            .and_then(|p| p.get(name))
+            .ok_or_else(|| ConfigError::NoProfile(name.to_string()))
+    }
+
+    /// The config at [ConfigFile::path] with the settings of the profile from
+    /// [ConfigFile::profile_name], if there is one, and the problems to warn
+    /// about. Fails on a config that cannot be parsed, has unknown keys or
+    /// lacks the profile. Without a config file the defaults are used, with a
+    /// [ConfigError::NotFound] warning, and the settings in use that
+    /// [ConfigFile::check] finds fault with are warnings too.
+    pub fn load(
+        path: Option<&Path>,
+        profile: Option<&str>,
+    ) -> Result<(ConfigFile, Vec<ConfigError>), ConfigError> {
+        let (config, mut warnings) = match ConfigFile::path(path) {
+            Ok(path) => {
+                let (config, unknown) = parse(&path)?;
+                if let Some(key) = unknown.into_iter().next() {
+                    return Err(ConfigError::UnknownKey { path, key });
+                }
+                (config, vec![])
+            }
+            Err(e @ ConfigError::NotFound(_)) => (ConfigFile::new(), vec![e]),
+            Err(e) => return Err(e),
+        };
+        match ConfigFile::profile_name(profile) {
+            Some(name) => {
+                let profile = config.profile(&name)?.clone();
+                warnings.extend(problems(&config, Some((&name, &profile))));
+                Ok((config.with_profile(profile), warnings))
+            }
+            None => {
+                warnings.extend(problems(&config, None));
+                Ok((config, warnings))
+            }
+        }
+    }
+
+    /// Every problem with the config: the top-level settings and those of the
+    /// profile from [ConfigFile::profile_name], or of all profiles if none is
+    /// named
+    pub fn check(path: Option<&Path>, profile: Option<&str>) -> Vec<ConfigError> {
+        let path = match ConfigFile::path(path) {
+            Ok(path) => path,
+            Err(e) => return vec![e],
+        };
+        let (config, unknown) = match parse(&path) {
+            Ok(parsed) => parsed,
+            Err(e) => return vec![e],
+        };
+        let mut errors: Vec<ConfigError> = unknown
+            .into_iter()
+            .map(|key| ConfigError::UnknownKey {
+                path: path.clone(),
+                key,
+            })
+            .collect();
+        match ConfigFile::profile_name(profile) {
+            Some(name) => match config.profile(&name) {
+                Ok(profile) => errors.extend(problems(&config, Some((&name, profile)))),
+                Err(e) => errors.push(e),
+            },
+            None => {
+                errors.extend(problems(&config, None));
+                let mut names: Vec<&String> =
+                    config.profiles.iter().flatten().map(|(n, _)| n).collect();
+                names.sort();
+                for name in names {
+                    let profile = config.profile(name).unwrap();
+                    errors.extend(problems(&ConfigFile::new(), Some((name, profile))));
+                }
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+    use std::sync::Mutex;
+
+    /// The tests that set the environment variables of the config take turns
+    static ENV: Mutex<()> = Mutex::new(());
+
+    fn write_config(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("gtd-{}-{}.json", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn load_warns_about_missing_dirs_and_hosts() {
+        let path = write_config(
+            "missing-dir",
+            r#"{"default_dirs": ["/nonexistent/kb"], "server": {"host": "localhost", "user": "u", "psw": "p"}}"#,
+        );
+        let loaded = ConfigFile::load(Some(&path), None);
+        let problems = ConfigFile::check(Some(&path), None);
+        fs::remove_file(&path).unwrap();
+
+        let (_, warnings) = loaded.unwrap();
+        assert!(matches!(
+            warnings[..],
+            [ConfigError::MissingDir { .. }, ConfigError::BadHost { .. }]
+        ));
+        assert_eq!(problems.len(), 2);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn writable_checks_permissions_without_writing() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = temp_dir("config-writable");
+        let inbox = dir.join("inbox.md");
+        fs::write(&inbox, "").unwrap();
+        assert!(writable(&inbox).is_ok());
+        assert!(writable(&dir.join("new.md")).is_ok());
+        assert!(writable(&dir.join("missing/inbox.md")).is_err());
+
+        fs::set_permissions(&inbox, fs::Permissions::from_mode(0o444)).unwrap();
+        assert!(writable(&inbox).is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn load_refuses_unknown_keys_and_profiles() {
+        let unknown_key = write_config("unknown-key", r#"{"default_dir": ["/tmp"]}"#);
+        let no_profiles = write_config("no-profiles", "{}");
+        let unknown = ConfigFile::load(Some(&unknown_key), None);
+        let no_profile = ConfigFile::load(Some(&no_profiles), Some("nope"));
+        fs::remove_file(&unknown_key).unwrap();
+        fs::remove_file(&no_profiles).unwrap();
+
+        assert!(
+            matches!(unknown, Err(ConfigError::UnknownKey { key, .. }) if key == "default_dir")
+        );
+        assert!(matches!(no_profile, Err(ConfigError::NoProfile(name)) if name == "nope"));
+    }
+
+    #[test]
+    fn flag_beats_env_beats_default_file() {
+        let _env = ENV.lock().unwrap();
+        let dir = temp_dir("config-precedence");
+        let default = dir.join("gtd/config.json");
+        fs::create_dir_all(default.parent().unwrap()).unwrap();
+        fs::write(&default, "{}").unwrap();
+        let from_env = dir.join("env.json");
+        let from_flag = dir.join("flag.json");
+        std::env::set_var("XDG_CONFIG_HOME", &dir);
+        std::env::remove_var("GTD_CONFIG");
+        std::env::remove_var("GTD_PROFILE");
+
+        assert_eq!(ConfigFile::path(None).unwrap(), default);
+        assert_eq!(ConfigFile::state_key(None, None), None);
+        assert_eq!(ConfigFile::profile_name(None), None);
+
+        std::env::set_var("GTD_CONFIG", &from_env);
+        std::env::set_var("GTD_PROFILE", "work");
+        assert_eq!(ConfigFile::path(None).unwrap(), from_env);
+        assert_eq!(ConfigFile::path(Some(&from_flag)).unwrap(), from_flag);
+        assert_eq!(ConfigFile::profile_name(None).as_deref(), Some("work"));
+        assert_eq!(
+            ConfigFile::profile_name(Some("home")).as_deref(),
+            Some("home")
+        );
+
+        // The default config given by its path keeps the default state
+        assert_eq!(
+            ConfigFile::state_key(Some(&default), None).as_deref(),
+            Some("work")
+        );
+        let env_key = ConfigFile::state_key(None, Some("home")).unwrap();
+        let flag_key = ConfigFile::state_key(Some(&from_flag), Some("home")).unwrap();
+        assert!(env_key.ends_with("-home") && flag_key.ends_with("-home"));
+        assert_ne!(env_key, flag_key);
+        assert_eq!(
+            ConfigFile::state_key(None, None).unwrap(),
+            env_key.replace("-home", "-work")
+        );
+
+        std::env::remove_var("GTD_CONFIG");
+        std::env::remove_var("GTD_PROFILE");
+        std::env::remove_var("XDG_CONFIG_HOME");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,304 @@
+use crate::archive::ARCHIVE_HEADING;
+use crate::edit::EditFile;
+use crate::model::{ConfigFile, Task, TaskDates, TaskSource, TaskStatus};
+use crate::outline::{dedent, item_block, sections, LIST_ITEM_RE};
+use chrono::{DateTime, Local, NaiveDate};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The day a file was last modified
+pub fn modified_on(path: &Path) -> Option<NaiveDate> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(DateTime::<Local>::from(modified).date_naive())
+}
+
+/// The project a file holds: its file name
+pub fn project_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Files matching these globs are crawled unless `include_globs` is set
+pub const DEFAULT_INCLUDE: &[&str] = &["**/*.md"];
+
+/// Like `.gitignore`, a file in any directory of the knowledge base that lists
+/// what is not crawled
+pub const GTDIGNORE: &str = ".gtdignore";
+
+/// Which files in the knowledge base are crawled
+#[derive(Debug, Clone, Default)]
+pub struct CrawlSettings {
+    /// File names that are never projects
+    pub ignore_files: Vec<String>,
+    /// Globs of the files to crawl, relative to the knowledge base root.
+    /// [DEFAULT_INCLUDE] when empty.
+    pub include_globs: Vec<String>,
+    /// Globs of the files and directories to leave out, e.g. `archive/**`
+    pub exclude_globs: Vec<String>,
+    /// How many directories deep to go, 1 for only the files in the root
+    pub max_depth: Option<usize>,
+    /// Leave out what `.gitignore` files in git repositories list
+    pub gitignore: bool,
+    /// Files that are never projects, like the archive
+    pub exclude_paths: Vec<PathBuf>,
+}
+
+impl CrawlSettings {
+    pub fn new(config: &ConfigFile) -> CrawlSettings {
+        CrawlSettings {
+            ignore_files: config.ignore_files.clone().unwrap_or_default(),
+            include_globs: config.include_globs.clone().unwrap_or_default(),
+            exclude_globs: config.exclude_globs.clone().unwrap_or_default(),
+            max_depth: config.max_depth,
+            gitignore: config.gitignore.unwrap_or(false),
+            exclude_paths: config.archive_path.iter().map(PathBuf::from).collect(),
+        }
+    }
+}
+
+/// A glob with `*` not matching `/`, as in `.gitignore`
+pub fn glob(glob: &str) -> Result<Glob, globset::Error> {
+    GlobBuilder::new(glob).literal_separator(true).build()
+}
+
+/// The globs that are valid, see [crate::config] for the others
+fn glob_set<S: AsRef<str>>(globs: &[S]) -> GlobSet {
+    let mut set = GlobSetBuilder::new();
+    for g in globs.iter().filter_map(|g| glob(g.as_ref()).ok()) {
+        set.add(g);
+    }
+    set.build().unwrap_or_else(|_| GlobSet::empty())
+}
+
+/// All files in the knowledge base that are considered to be projects
+pub fn project_files(dirs: &[PathBuf], settings: &CrawlSettings) -> Vec<PathBuf> {
+    let include = match settings.include_globs.is_empty() {
+        true => glob_set(DEFAULT_INCLUDE),
+        false => glob_set(&settings.include_globs),
+    };
+    let exclude = glob_set(&settings.exclude_globs);
+    let exclude_paths: Vec<PathBuf> = settings
+        .exclude_paths
+        .iter()
+        .filter_map(|p| fs::canonicalize(p).ok())
+        .collect();
+    dirs.iter()
+        .flat_map(|dir| {
+            let (root, excluded_dirs) = (dir.clone(), exclude.clone());
+            WalkBuilder::new(dir)
+                .hidden(true)
+                .ignore(false)
+                .git_ignore(settings.gitignore)
+                .git_global(settings.gitignore)
+                .git_exclude(settings.gitignore)
+                .max_depth(settings.max_depth)
+                .add_custom_ignore_filename(GTDIGNORE)
+                .filter_entry(move |e| {
+                    let is_dir = e.file_type().is_some_and(|t| t.is_dir());
+                    let path = e.path().strip_prefix(&root).unwrap_or(e.path());
+                    e.depth() == 0 || !is_dir || !excluded_dirs.is_match(path)
+                })
+                .build()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+                .filter(|e| {
+                    !settings
+                        .ignore_files
+                        .contains(&e.file_name().to_str().unwrap_or("").to_string())
+                })
+                .filter(|e| {
+                    let path = e.path().strip_prefix(dir).unwrap_or(e.path());
+                    include.is_match(path) && !exclude.is_match(path)
+                })
+                .map(|e| e.into_path())
+                .collect::<Vec<_>>()
+        })
+        .filter(|p| {
+            exclude_paths.is_empty()
+                || !fs::canonicalize(p)
+                    .map(|p| exclude_paths.contains(&p))
+                    .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// Parse all tasks in a single project file.
+///
+/// A file whose first list item starts with `- @gtd` is a GTD project: every
+/// list item in it is a task and inherits the status, dates and contexts of
+/// that first item. In any other file only list items with flags are tasks.
+/// Items in an archive section are never tasks.
+pub fn read_tasks(path: &Path) -> Vec<Task> {
+    let file_name = project_name(path);
+    let file_content = fs::read_to_string(path).unwrap_or("".to_string());
+    let lines: Vec<String> = file_content.lines().map(String::from).collect();
+    let archived = sections(&lines, ARCHIVE_HEADING);
+
+    let task_lines = lines
+        .iter()
+        .map(String::as_str)
+        .enumerate()
+        .filter(|(i, _)| !archived.iter().any(|r| r.contains(i)))
+        .filter(|(_, line)| LIST_ITEM_RE.is_match(line));
+    let with_source = |i: usize, mut t: Task| {
+        t.source = Some(TaskSource {
+            path: path.to_path_buf(),
+            line: i + 1,
+        });
+        t
+    };
+
+    let first_line = task_lines.clone().next().map(|(_, l)| l).unwrap_or("");
+    if first_line.starts_with("- @gtd") {
+        let gt = Task::from(first_line, &file_name);
+        task_lines
+            .filter(|(_, l)| !l.starts_with("- @gtd"))
+            .map(|(i, l)| {
+                let mut t = Task::from(l, &file_name);
+                if t.status == TaskStatus::NoStatus {
+                    // Replace NoStatus with GTD task status
+                    t.status = gt.status;
+                }
+
+                let start = t
+                    .dates
+                    .as_ref()
+                    .and_then(|d| d.start.clone())
+                    .or(gt.dates.as_ref().and_then(|d| d.start.clone()));
+                let due = t
+                    .dates
+                    .as_ref()
+                    .and_then(|d| d.due.clone())
+                    .or(gt.dates.as_ref().and_then(|d| d.due.clone()));
+                let visible = t
+                    .dates
+                    .as_ref()
+                    .and_then(|d| d.visible.clone())
+                    .or(gt.dates.as_ref().and_then(|d| d.visible.clone()));
+                let completed = t.dates.as_ref().and_then(|d| d.completed.clone());
+                t.dates = match (start, due, visible, completed) {
+                    (None, None, None, None) => None,
+                    (s, d, v, c) => Some(TaskDates {
+                        start: s,
+                        due: d,
+                        visible: v,
+                        completed: c,
+                    }),
+                };
+                t.contexts.append(gt.contexts.clone().as_mut());
+                with_source(i, t)
+            })
+            .collect()
+    } else {
+        let re = Task::re_any();
+        task_lines
+            .filter(|(_, line)| re.is_match(line))
+            .map(|(i, l)| with_source(i, Task::from(l, &file_name)))
+            .filter(|task| !task.has_noflags())
+            .collect()
+    }
+}
+
+/// The project files read so far, to look up the notes of many tasks without
+/// reading their file for each
+#[derive(Debug, Default)]
+pub struct NotesCache {
+    files: HashMap<PathBuf, Option<EditFile>>,
+}
+
+impl NotesCache {
+    /// The nested items and notes under a task
+    pub fn notes(&mut self, task: &Task) -> Vec<String> {
+        let Some(source) = &task.source else {
+            return vec![];
+        };
+        let file = self
+            .files
+            .entry(source.path.clone())
+            .or_insert_with(|| EditFile::open(&source.path).ok());
+        match file.as_ref().map(|f| (f, f.task_line(task))) {
+            Some((file, Ok(i))) => dedent(&file.lines[item_block(&file.lines, i)])
+                .into_iter()
+                .skip(1)
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    #[test]
+    fn gtd_project_items_inherit_from_the_first_item() {
+        let dir = std::env::temp_dir().join(format!("gtd-crawl-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("house.md");
+        fs::write(
+            &path,
+            "- @gtd @wip #xhome @v20261001\n- write report @d20261025\n- paint door @v20261101\n",
+        )
+        .unwrap();
+        let tasks = read_tasks(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(tasks.len(), 2);
+        let report = tasks[0].dates.as_ref().unwrap();
+        assert_eq!(tasks[0].status, TaskStatus::Wip);
+        assert_eq!(tasks[0].contexts, vec!["#xhome".to_string()]);
+        assert_eq!(report.due.as_deref(), Some("20261025"));
+        assert_eq!(report.visible.as_deref(), Some("20261001"));
+        let door = tasks[1].dates.as_ref().unwrap();
+        assert_eq!(door.due, None);
+        assert_eq!(door.visible.as_deref(), Some("20261101"));
+    }
+
+    #[test]
+    fn project_files_follow_the_crawl_settings() {
+        let dir = temp_dir("crawl-settings");
+        for file in [
+            "house.md",
+            "someday.md",
+            "notes.txt",
+            "drafts/plan.md",
+            "archive/old.md",
+            "work/report.md",
+            "work/deep/task.md",
+        ] {
+            fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            fs::write(dir.join(file), "- @todo task\n").unwrap();
+        }
+        fs::create_dir(dir.join(".git")).unwrap();
+        fs::write(dir.join(".gitignore"), "drafts/\n").unwrap();
+        fs::write(dir.join(GTDIGNORE), "someday.md\n").unwrap();
+        let crawl = |settings: &CrawlSettings| {
+            let mut files: Vec<String> = project_files(std::slice::from_ref(&dir), settings)
+                .iter()
+                .map(|p| p.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+                .collect();
+            files.sort();
+            files
+        };
+
+        let settings = CrawlSettings {
+            exclude_globs: vec!["archive/**".into()],
+            max_depth: Some(2),
+            gitignore: true,
+            ..Default::default()
+        };
+        assert_eq!(crawl(&settings), vec!["house.md", "work/report.md"]);
+
+        let settings = CrawlSettings {
+            include_globs: vec!["*.txt".into(), "drafts/*.md".into(), "*day.md".into()],
+            ..Default::default()
+        };
+        assert_eq!(crawl(&settings), vec!["drafts/plan.md", "notes.txt"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
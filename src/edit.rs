@@ -0,0 +1,267 @@
+use crate::config::ConfigError;
+use crate::model::{Task, TaskSource};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug)]
+pub enum EditError {
+    Io(PathBuf, io::Error),
+    /// The file changed on disk since it was read, e.g. saved from an editor
+    Changed(PathBuf),
+    /// The task is no longer at the line it was found on
+    TaskMoved(TaskSource),
+    NoSource(String),
+    /// There is no data directory to keep the state of gtd in
+    NoDataDir(ConfigError),
+}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            Self::Changed(path) => write!(
+                f,
+                "{} changed since it was read, not overwriting it",
+                path.display()
+            ),
+            Self::TaskMoved(source) => write!(f, "the task is no longer at {}", source),
+            Self::NoSource(description) => write!(f, "'{}' has no source location", description),
+            Self::NoDataDir(e) => write!(f, "no data directory, {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Fingerprint {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl Fingerprint {
+    fn of(content: &str, metadata: &fs::Metadata) -> Fingerprint {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        Fingerprint {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            hash: hasher.finish(),
+        }
+    }
+}
+
+/// A text file loaded as lines for editing.
+///
+/// Saving keeps the line endings and trailing newline of the original, writes
+/// to a temporary file that is renamed over the original, and refuses to save
+/// if the file changed on disk since it was read.
+#[derive(Debug, Clone)]
+pub struct EditFile {
+    pub path: PathBuf,
+    pub lines: Vec<String>,
+    line_ending: &'static str,
+    trailing_newline: bool,
+    /// The content as read, `None` when the file did not exist yet
+    original: Option<String>,
+    fingerprint: Option<Fingerprint>,
+}
+
+impl EditFile {
+    pub fn open(path: &Path) -> Result<EditFile, EditError> {
+        let io_err = |e| EditError::Io(path.to_path_buf(), e);
+        let content = fs::read_to_string(path).map_err(io_err)?;
+        let metadata = fs::metadata(path).map_err(io_err)?;
+        Ok(EditFile {
+            path: path.to_path_buf(),
+            lines: content.lines().map(String::from).collect(),
+            line_ending: if content.contains("\r\n") {
+                "\r\n"
+            } else {
+                "\n"
+            },
+            trailing_newline: content.is_empty() || content.ends_with('\n'),
+            fingerprint: Some(Fingerprint::of(&content, &metadata)),
+            original: Some(content),
+        })
+    }
+
+    /// Open the file, or start an empty one if it does not exist yet
+    pub fn open_or_create(path: &Path) -> Result<EditFile, EditError> {
+        if path.exists() {
+            return EditFile::open(path);
+        }
+        Ok(EditFile {
+            path: path.to_path_buf(),
+            lines: vec![],
+            line_ending: "\n",
+            trailing_newline: true,
+            original: None,
+            fingerprint: None,
+        })
+    }
+
+    pub fn original(&self) -> Option<&str> {
+        self.original.as_deref()
+    }
+
+    pub fn content(&self) -> String {
+        let mut content = self.lines.join(self.line_ending);
+        if self.trailing_newline && !self.lines.is_empty() {
+            content.push_str(self.line_ending);
+        }
+        content
+    }
+
+    /// Fail if the file on disk is no longer the one that was read
+    pub fn check(&self) -> Result<(), EditError> {
+        let changed = || EditError::Changed(self.path.clone());
+        let Some(fingerprint) = &self.fingerprint else {
+            return match self.path.exists() {
+                true => Err(changed()),
+                false => Ok(()),
+            };
+        };
+        let metadata = fs::metadata(&self.path).map_err(|_| changed())?;
+        if metadata.modified().ok() == fingerprint.modified && metadata.len() == fingerprint.len {
+            return Ok(());
+        }
+        let content = fs::read_to_string(&self.path).map_err(|_| changed())?;
+        match Fingerprint::of(&content, &metadata).hash == fingerprint.hash {
+            true => Ok(()),
+            false => Err(changed()),
+        }
+    }
+
+    pub fn save(&mut self) -> Result<(), EditError> {
+        self.check()?;
+        let content = self.content();
+        write_atomic(&self.path, &content)?;
+
+        let metadata = fs::metadata(&self.path).map_err(|e| EditError::Io(self.path.clone(), e))?;
+        self.fingerprint = Some(Fingerprint::of(&content, &metadata));
+        self.original = Some(content);
+        Ok(())
+    }
+
+    /// The index of the line `task` was parsed from, provided it is still there
+    pub fn task_line(&self, task: &Task) -> Result<usize, EditError> {
+        let source = task
+            .source
+            .as_ref()
+            .ok_or(EditError::NoSource(task.title()))?;
+        let i = source.line - 1;
+        match self.lines.get(i) {
+            Some(line) if Task::from(line, &task.project).description == task.description => Ok(i),
+            _ => Err(EditError::TaskMoved(source.clone())),
+        }
+    }
+}
+
+/// Replace the content of a file by writing a temporary file next to it and
+/// renaming that over the original
+pub fn write_atomic(path: &Path, content: &str) -> Result<(), EditError> {
+    let io_err = |e| EditError::Io(path.to_path_buf(), e);
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io_err(io::Error::new(io::ErrorKind::InvalidInput, "not a file")))?
+        .to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{}.gtd-tmp", file_name));
+    fs::write(&tmp_path, content).map_err(io_err)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(&tmp_path, metadata.permissions()).map_err(io_err)?;
+    }
+    if let Err(e) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(io_err(e));
+    }
+    Ok(())
+}
+
+/// Save several files, but only if none of them changed since they were read
+pub fn save_all(files: &mut [&mut EditFile]) -> Result<(), EditError> {
+    for file in files.iter() {
+        file.check()?;
+    }
+    for file in files.iter_mut() {
+        file.save()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    fn round_trip(name: &str, content: &str) {
+        let dir = temp_dir(name);
+        let path = dir.join("house.md");
+        fs::write(&path, content).unwrap();
+        let mut file = EditFile::open(&path).unwrap();
+        assert_eq!(file.content(), content);
+        file.lines[0].push_str(" @wip");
+        file.save().unwrap();
+        file.lines[0] = file.lines[0].replace(" @wip", "");
+        file.save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        round_trip("edit-crlf", "- paint door\r\n- fix roof\r\n");
+    }
+
+    #[test]
+    fn keeps_a_missing_trailing_newline() {
+        round_trip("edit-no-newline", "- paint door\n- fix roof");
+    }
+
+    #[test]
+    fn refuses_to_save_over_changes_on_disk() {
+        let dir = temp_dir("edit-changed");
+        let path = dir.join("house.md");
+        fs::write(&path, "- paint door\n").unwrap();
+        let mut file = EditFile::open(&path).unwrap();
+        fs::write(&path, "- paint door\n- fix roof\n").unwrap();
+        file.lines.push("- mow lawn".into());
+
+        assert!(matches!(file.save(), Err(EditError::Changed(p)) if p == path));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "- paint door\n- fix roof\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn creates_a_missing_file() {
+        let dir = temp_dir("edit-create");
+        let path = dir.join("inbox.md");
+        let mut file = EditFile::open_or_create(&path).unwrap();
+        assert_eq!(file.original(), None);
+        file.lines.push("- call mom".into());
+        file.save().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "- call mom\n");
+        assert!(matches!(
+            EditFile::open_or_create(&dir.join("missing/inbox.md")).map(|mut f| f.save()),
+            Ok(Err(EditError::Io(..)))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomic_needs_a_file_name() {
+        assert!(matches!(
+            write_atomic(Path::new(".."), ""),
+            Err(EditError::Io(..))
+        ));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::crawl::NotesCache;
+use crate::fuzzy;
+use crate::model::Task;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Description,
+    Project,
+    Notes,
+}
+
+/// Text of a task that the search matched, with the char positions to highlight
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub field: Field,
+    pub text: String,
+    pub positions: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Found {
+    pub score: i64,
+    pub task: Task,
+    pub hits: Vec<Hit>,
+}
+
+/// Match every word against the title, project and notes of a task, keeping
+/// the best match of each. Matches in the notes count for half.
+fn find_in(words: &[String], task: &Task, notes: &mut NotesCache) -> Option<Found> {
+    let texts: Vec<(Field, String)> = [
+        (Field::Description, task.title()),
+        (Field::Project, task.project.clone()),
+    ]
+    .into_iter()
+    .chain(
+        notes
+            .notes(task)
+            .into_iter()
+            .map(|n| (Field::Notes, n.trim().to_string())),
+    )
+    .collect();
+
+    let mut score = 0;
+    let mut hits: Vec<Hit> = vec![];
+    for word in words {
+        let (word_score, i, positions) = texts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (field, text))| {
+                let (s, positions) = fuzzy::matches(word, text)?;
+                let s = match field {
+                    Field::Notes => s / 2,
+                    _ => s,
+                };
+                Some((s, i, positions))
+            })
+            .max_by_key(|(s, i, _)| (*s, std::cmp::Reverse(*i)))?;
+        score += word_score;
+        let (field, text) = &texts[i];
+        match hits
+            .iter_mut()
+            .find(|h| h.field == *field && h.text == *text)
+        {
+            Some(hit) => hit.positions.extend(positions),
+            None => hits.push(Hit {
+                field: *field,
+                text: text.clone(),
+                positions,
+            }),
+        }
+    }
+    Some(Found {
+        score,
+        task: task.clone(),
+        hits,
+    })
+}
+
+/// The tasks matching all `words`, best match first
+pub fn find(words: &[String], tasks: &[Task]) -> Vec<Found> {
+    let mut notes = NotesCache::default();
+    let mut found: Vec<Found> = tasks
+        .iter()
+        .filter_map(|t| find_in(words, t, &mut notes))
+        .collect();
+    found.sort_by_key(|f| std::cmp::Reverse(f.score));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawl::read_tasks;
+    use crate::testing::temp_dir;
+    use std::fs;
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn every_word_must_match_somewhere() {
+        let dir = temp_dir("find");
+        let path = dir.join("house.md");
+        fs::write(
+            &path,
+            "- paint door @todo\n  - buy blue paint\n- fix roof @todo\n- call painter @wip\n",
+        )
+        .unwrap();
+        let tasks = read_tasks(&path);
+
+        let found = find(&words("paint blue"), &tasks);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].task.title(), "paint door");
+        let fields: Vec<(Field, &str)> = found[0]
+            .hits
+            .iter()
+            .map(|h| (h.field, h.text.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                (Field::Description, "paint door"),
+                (Field::Notes, "- buy blue paint")
+            ]
+        );
+        assert!(find(&words("paint green"), &tasks).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn notes_count_for_less_than_the_description() {
+        let dir = temp_dir("find-notes");
+        let path = dir.join("house.md");
+        fs::write(
+            &path,
+            "- fix roof @todo\n  - ask the plumber\n- call plumber @todo\n",
+        )
+        .unwrap();
+        let tasks = read_tasks(&path);
+        let mut notes = NotesCache::default();
+        let roof = find_in(&words("plumber"), &tasks[0], &mut notes).unwrap();
+        let call = find_in(&words("plumber"), &tasks[1], &mut notes).unwrap();
+        assert_eq!(roof.score * 2, call.score);
+
+        let found: Vec<String> = find(&words("plumber"), &tasks)
+            .iter()
+            .map(|f| f.task.title())
+            .collect();
+        assert_eq!(found, ["call plumber", "fix roof"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
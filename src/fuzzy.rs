@@ -0,0 +1,69 @@
+/// Score how well `pattern` matches `candidate` as a case-insensitive
+/// subsequence, ignoring whitespace in the pattern. Consecutive characters and
+/// characters at the start of a word score higher. `None` if it does not match.
+pub fn score(pattern: &str, candidate: &str) -> Option<i64> {
+    matches(pattern, candidate).map(|(score, _)| score)
+}
+
+/// The [score] of a match with the char positions in `candidate` that matched
+pub fn matches(pattern: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let lower = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let pattern: Vec<char> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(lower)
+        .collect();
+    let candidate: Vec<char> = candidate.chars().map(lower).collect();
+
+    let mut score = 0;
+    let mut positions = vec![];
+    for p in pattern {
+        let next = positions.last().map(|i| i + 1).unwrap_or(0);
+        let i = (next..candidate.len()).find(|&i| candidate[i] == p)?;
+        score += 1;
+        if i > 0 && positions.last() == Some(&(i - 1)) {
+            score += 5;
+        }
+        if i == 0 || !candidate[i - 1].is_alphanumeric() {
+            score += 3;
+        }
+        positions.push(i);
+    }
+    Some((score, positions))
+}
+
+/// All items matching `pattern`, best match first
+pub fn rank<'a, T>(pattern: &str, items: &'a [T], key: impl Fn(&T) -> String) -> Vec<(i64, &'a T)> {
+    let mut ranked: Vec<(i64, &T)> = items
+        .iter()
+        .filter_map(|item| score(pattern, &key(item)).map(|s| (s, item)))
+        .collect();
+    ranked.sort_by_key(|(s, _)| std::cmp::Reverse(*s));
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_a_subsequence_ignoring_case_and_spaces() {
+        assert_eq!(matches("PD", "paint door"), Some((8, vec![0, 6])));
+        assert_eq!(
+            matches("pa in", "paint door").map(|m| m.1),
+            Some(vec![0, 1, 2, 3])
+        );
+        assert_eq!(matches("dp", "paint door"), None);
+        assert_eq!(score("", "paint door"), Some(0));
+    }
+
+    #[test]
+    fn ranks_consecutive_and_word_start_matches_first() {
+        let items = ["repaint", "plan it", "paint door", "unrelated"];
+        let ranked: Vec<&str> = rank("pai", &items, |s| s.to_string())
+            .into_iter()
+            .map(|(_, s)| *s)
+            .collect();
+        assert_eq!(ranked, ["paint door", "repaint", "plan it"]);
+    }
+}
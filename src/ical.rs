@@ -0,0 +1,177 @@
+use crate::model::{Task, TaskDates, TaskStatus};
+use chrono::{NaiveDate, Utc};
+use uuid::Uuid;
+
+/// The calendar component each task becomes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    /// A to-do with its due and start dates
+    Todo,
+    /// An all-day event on the due date, or else the start date
+    Event,
+}
+
+/// An id that stays the same as long as the file, project and title of the
+/// task do, whatever else is exported with it. Tasks with the same title in
+/// the same file share it.
+pub fn task_uid(task: &Task) -> String {
+    let path = task
+        .source
+        .as_ref()
+        .map(|s| s.path.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let uid = Uuid::new_v5(&task.stable_id(), path.as_bytes());
+    format!("{}@gtd-cli", uid)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Split a content line into lines of at most 75 bytes, as the spec asks
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            len = 1;
+        }
+        folded.push(c);
+        len += c.len_utf8();
+    }
+    folded + "\r\n"
+}
+
+fn date(date: &Option<String>) -> Option<NaiveDate> {
+    date.as_deref().and_then(TaskDates::to_date)
+}
+
+fn component(task: &Task, uid: String, component: Component, stamp: &str) -> Option<Vec<String>> {
+    let dates = task.dates.as_ref()?;
+    let (start, due) = (date(&dates.start), date(&dates.due));
+    let value_date =
+        |name: &str, date: NaiveDate| format!("{};VALUE=DATE:{}", name, date.format("%Y%m%d"));
+
+    let mut lines = vec![
+        format!("UID:{}", uid),
+        format!("DTSTAMP:{}", stamp),
+        format!("SUMMARY:{}", escape(&task.title())),
+    ];
+    match component {
+        Component::Todo => {
+            if start.is_none() && due.is_none() {
+                return None;
+            }
+            lines.extend(start.map(|d| value_date("DTSTART", d)));
+            lines.extend(due.map(|d| value_date("DUE", d)));
+            lines.push(format!(
+                "STATUS:{}",
+                match task.status {
+                    TaskStatus::Done => "COMPLETED",
+                    TaskStatus::Wip | TaskStatus::Review => "IN-PROCESS",
+                    _ => "NEEDS-ACTION",
+                }
+            ));
+            if let Some(completed) = date(&dates.completed) {
+                lines.push(format!("COMPLETED:{}T000000Z", completed.format("%Y%m%d")));
+            }
+        }
+        Component::Event => {
+            let day = due.or(start)?;
+            lines.push(value_date("DTSTART", day));
+            lines.push(value_date("DTEND", day.succ_opt()?));
+            lines.push("TRANSP:TRANSPARENT".into());
+        }
+    }
+    if !task.contexts.is_empty() {
+        let categories: Vec<String> = task
+            .contexts
+            .iter()
+            .map(|c| escape(c.trim_start_matches("#x")))
+            .collect();
+        lines.push(format!("CATEGORIES:{}", categories.join(",")));
+    }
+    if let Some(priority) = task.priority {
+        // A to I map to the priorities 1 (highest) to 9
+        let priority = (priority as u32 - 'A' as u32 + 1).min(9);
+        lines.push(format!("PRIORITY:{}", priority));
+    }
+    let mut description = task.project.clone();
+    if let Some(source) = &task.source {
+        description = format!("{}\n{}", description, source);
+    }
+    lines.push(format!("DESCRIPTION:{}", escape(&description)));
+    Some(lines)
+}
+
+/// An iCalendar file with a to-do or event for every task with a due or
+/// start date. Done tasks are left out of the events.
+pub fn to_ics(tasks: &[Task], kind: Component) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let name = match kind {
+        Component::Todo => "VTODO",
+        Component::Event => "VEVENT",
+    };
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".into(),
+        "PRODID:-//gtd-cli//EN".into(),
+    ];
+    for task in tasks {
+        if kind == Component::Event && task.status == TaskStatus::Done {
+            continue;
+        }
+        if let Some(properties) = component(task, task_uid(task), kind, &stamp) {
+            lines.push(format!("BEGIN:{}", name));
+            lines.extend(properties);
+            lines.push(format!("END:{}", name));
+        }
+    }
+    lines.push("END:VCALENDAR".into());
+    lines.iter().map(|l| fold(l)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::TaskSource;
+
+    fn uids(lines: &[&str]) -> Vec<String> {
+        let tasks: Vec<Task> = lines.iter().map(|l| Task::from(l, "house.md")).collect();
+        to_ics(&tasks, Component::Todo)
+            .lines()
+            .filter_map(|l| l.strip_prefix("UID:"))
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn uid_does_not_change_with_the_status() {
+        let open = uids(&["- [ ] paint door @d20241031"]);
+        let wip = uids(&["- [ ] paint door @wip @d20241031"]);
+        let done = uids(&["- [x] paint door @d20241031 @c20241020"]);
+        assert_eq!(open, wip);
+        assert_eq!(open, done);
+    }
+
+    #[test]
+    fn uid_depends_on_the_file_and_not_on_the_other_tasks() {
+        let in_file = |path: &str| {
+            let mut task = Task::from("- paint door @d20241031", "house.md");
+            task.source = Some(TaskSource {
+                path: path.into(),
+                line: 3,
+            });
+            task
+        };
+        let (task, other) = (in_file("kb/house.md"), in_file("old/house.md"));
+        let uid = format!("UID:{}", task_uid(&task));
+        assert!(to_ics(std::slice::from_ref(&task), Component::Todo).contains(&uid));
+        assert!(to_ics(&[other.clone(), task.clone()], Component::Todo).contains(&uid));
+        assert_ne!(task_uid(&task), task_uid(&other));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::config::ConfigError;
+use crate::edit::{save_all, write_atomic, EditError, EditFile};
+use crate::model::data_dir;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// How many entries the journal keeps when it is trimmed
+const MAX_ENTRIES: usize = 200;
+
+/// The journal is only read back and trimmed once it grows past this size
+const MAX_BYTES: u64 = 1 << 20;
+
+/// The lines an edit replaced in a file, so it can be reversed without storing
+/// the whole file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: PathBuf,
+    /// The edit created the file, so undoing it removes the file
+    pub created: bool,
+    /// Index of the first line that changed
+    pub start: usize,
+    /// The lines before the edit, with their line endings
+    pub removed: Vec<String>,
+    /// The lines that took their place
+    pub added: Vec<String>,
+    /// Checksum of the whole file after the edit
+    pub checksum: String,
+}
+
+impl FileChange {
+    fn new(path: &Path, before: Option<&str>, after: &str) -> FileChange {
+        let created = before.is_none();
+        let before: Vec<&str> = before.unwrap_or_default().split_inclusive('\n').collect();
+        let after_lines: Vec<&str> = after.split_inclusive('\n').collect();
+        let start = before
+            .iter()
+            .zip(&after_lines)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let end = before[start..]
+            .iter()
+            .rev()
+            .zip(after_lines[start..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let lines = |l: &[&str]| l.iter().map(|s| s.to_string()).collect();
+        FileChange {
+            path: path.to_path_buf(),
+            created,
+            start,
+            removed: lines(&before[start..before.len() - end]),
+            added: lines(&after_lines[start..after_lines.len() - end]),
+            checksum: checksum(after),
+        }
+    }
+
+    /// Put back the content of the file before the edit
+    fn revert(&self) -> Result<(), EditError> {
+        let io_err = |e| EditError::Io(self.path.clone(), e);
+        let content = fs::read_to_string(&self.path).map_err(io_err)?;
+        if self.created {
+            return fs::remove_file(&self.path).map_err(io_err);
+        }
+        let mut lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let end = (self.start + self.added.len()).min(lines.len());
+        lines.splice(self.start..end, self.removed.iter().map(String::as_str));
+        write_atomic(&self.path, &lines.concat())
+    }
+}
+
+fn checksum(content: &str) -> String {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, content.as_bytes())
+        .simple()
+        .to_string()
+}
+
+/// One reversible edit made by a gtd command, possibly across several files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: String,
+    pub time: String,
+    pub command: String,
+    pub files: Vec<FileChange>,
+}
+
+pub fn journal_path() -> Result<PathBuf, ConfigError> {
+    Ok(data_dir()?.join("journal.jsonl"))
+}
+
+pub fn read_journal(path: &Path) -> Vec<JournalEntry> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
+
+fn to_line(path: &Path, entry: &JournalEntry) -> Result<String, EditError> {
+    serde_json::to_string(entry)
+        .map(|line| line + "\n")
+        .map_err(|e| EditError::Io(path.to_path_buf(), io::Error::from(e)))
+}
+
+fn write_journal(path: &Path, entries: &[JournalEntry]) -> Result<(), EditError> {
+    let content = entries
+        .iter()
+        .map(|e| to_line(path, e))
+        .collect::<Result<String, _>>()?;
+    write_atomic(path, &content)
+}
+
+fn append(path: &Path, entry: &JournalEntry) -> Result<(), EditError> {
+    let io_err = |e| EditError::Io(path.to_path_buf(), e);
+    let line = to_line(path, entry)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io_err)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(io_err)?;
+    file.write_all(line.as_bytes()).map_err(io_err)?;
+
+    if file.metadata().map_err(io_err)?.len() > MAX_BYTES {
+        let entries = read_journal(path);
+        write_journal(path, &entries[entries.len().saturating_sub(MAX_ENTRIES)..])?;
+    }
+    Ok(())
+}
+
+/// Save the edited files and record the edit in the journal so it can be undone
+pub fn commit(command: &str, files: &mut [&mut EditFile]) -> Result<(), EditError> {
+    commit_to(
+        &journal_path().map_err(EditError::NoDataDir)?,
+        command,
+        files,
+    )
+}
+
+fn commit_to(path: &Path, command: &str, files: &mut [&mut EditFile]) -> Result<(), EditError> {
+    let changes: Vec<FileChange> = files
+        .iter()
+        .filter(|f| f.original() != Some(f.content().as_str()))
+        .map(|f| FileChange::new(&f.path, f.original(), &f.content()))
+        .collect();
+    if changes.is_empty() {
+        return Ok(());
+    }
+    save_all(files)?;
+    append(
+        path,
+        &JournalEntry {
+            id: Uuid::new_v4().to_string(),
+            time: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            command: command.to_string(),
+            files: changes,
+        },
+    )
+}
+
+/// Restore the files of the last `n` journal entries to their content before
+/// the edit, newest first. Stops at the first entry whose files were changed
+/// since, and returns the entries that were undone.
+pub fn undo(n: usize) -> Result<Vec<JournalEntry>, EditError> {
+    undo_from(&journal_path().map_err(EditError::NoDataDir)?, n)
+}
+
+fn undo_from(path: &Path, n: usize) -> Result<Vec<JournalEntry>, EditError> {
+    let mut entries = read_journal(path);
+    let mut undone = vec![];
+    while undone.len() < n {
+        let Some(entry) = entries.last() else {
+            break;
+        };
+        let unchanged = entry.files.iter().try_for_each(|change| {
+            match fs::read_to_string(&change.path).map(|c| checksum(&c)) {
+                Ok(sum) if sum == change.checksum => Ok(()),
+                _ => Err(EditError::Changed(change.path.clone())),
+            }
+        });
+        if let Err(e) = unchanged {
+            write_journal(path, &entries)?;
+            return Err(e);
+        }
+        for change in entry.files.iter().rev() {
+            change.revert()?;
+        }
+        undone.extend(entries.pop());
+    }
+    write_journal(path, &entries)?;
+    Ok(undone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    #[test]
+    fn undo_restores_a_multi_file_edit() {
+        let dir = temp_dir("journal-undo");
+        let journal = dir.join("journal.jsonl");
+        let house = dir.join("house.md");
+        let inbox = dir.join("inbox.md");
+        fs::write(&house, "# House\r\n- paint door\r\n- fix roof\r\n").unwrap();
+        fs::write(&inbox, "- call mom\n- mow lawn").unwrap();
+
+        let mut target = EditFile::open(&house).unwrap();
+        let mut source = EditFile::open(&inbox).unwrap();
+        let mut created = EditFile::open_or_create(&dir.join("archive.md")).unwrap();
+        target.lines.insert(2, "- mow lawn".into());
+        source.lines.pop();
+        created.lines.push("- call mom".into());
+        commit_to(
+            &journal,
+            "move",
+            &mut [&mut target, &mut source, &mut created],
+        )
+        .unwrap();
+        let entry = &read_journal(&journal)[0];
+        assert_eq!(entry.files[0].removed, Vec::<String>::new());
+        assert_eq!(entry.files[0].added, vec!["- mow lawn\r\n"]);
+
+        let undone = undo_from(&journal, 5).unwrap();
+        assert_eq!(undone.len(), 1);
+        assert_eq!(
+            fs::read_to_string(&house).unwrap(),
+            "# House\r\n- paint door\r\n- fix roof\r\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&inbox).unwrap(),
+            "- call mom\n- mow lawn"
+        );
+        assert!(!dir.join("archive.md").exists());
+        assert!(read_journal(&journal).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn undo_refuses_when_a_file_changed_since() {
+        let dir = temp_dir("journal-changed");
+        let journal = dir.join("journal.jsonl");
+        let house = dir.join("house.md");
+        fs::write(&house, "- paint door\n").unwrap();
+        let mut file = EditFile::open(&house).unwrap();
+        file.lines[0].push_str(" @done");
+        commit_to(&journal, "set done", &mut [&mut file]).unwrap();
+        fs::write(&house, "- paint door @done\n- fix roof\n").unwrap();
+
+        assert!(matches!(undo_from(&journal, 1), Err(EditError::Changed(p)) if p == house));
+        assert_eq!(
+            fs::read_to_string(&house).unwrap(),
+            "- paint door @done\n- fix roof\n"
+        );
+        assert_eq!(read_journal(&journal).len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
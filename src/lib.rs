@@ -1,2 +1,27 @@
-
-pub mod model;
\ No newline at end of file
+pub mod agenda;
+pub mod archive;
+pub mod completion;
+pub mod config;
+pub mod crawl;
+pub mod edit;
+pub mod find;
+pub mod fuzzy;
+pub mod ical;
+pub mod journal;
+pub mod limits;
+pub mod listing;
+pub mod model;
+pub mod outline;
+pub mod query;
+pub mod refile;
+pub mod render;
+pub mod review;
+pub mod snapshot;
+pub mod stats;
+pub mod taskwarrior;
+#[cfg(test)]
+mod testing;
+pub mod todotxt;
+pub mod tui;
+pub mod update;
+pub mod view;
@@ -0,0 +1,186 @@
+use crate::model::{Task, TaskStatus};
+use crate::query::{context_marker, parse_status};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+
+/// A status as written in the config, e.g. `wip`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StatusKey(pub TaskStatus);
+
+impl<'de> Deserialize<'de> for StatusKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse_status(&s)
+            .map(StatusKey)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// The most tasks allowed per status, over the whole knowledge base and per
+/// context, e.g. `{"wip": 3, "review": 5, "contexts": {"home": {"wip": 1}}}`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WipLimits {
+    #[serde(flatten)]
+    pub statuses: HashMap<StatusKey, usize>,
+    #[serde(default)]
+    pub contexts: HashMap<String, HashMap<StatusKey, usize>>,
+}
+
+/// A limit with the number of tasks it applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitCount {
+    pub status: TaskStatus,
+    /// The context marker, `None` for a global limit
+    pub context: Option<String>,
+    pub count: usize,
+    pub limit: usize,
+}
+
+impl LimitCount {
+    pub fn is_exceeded(&self) -> bool {
+        self.count > self.limit
+    }
+}
+
+impl std::fmt::Display for LimitCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let context = match &self.context {
+            Some(c) => format!(" in {}", c),
+            None => String::new(),
+        };
+        write!(
+            f,
+            "{} {} tasks{}, the limit is {}",
+            self.count, self.status, context, self.limit
+        )
+    }
+}
+
+impl WipLimits {
+    /// Every limit as (status, context marker, limit)
+    fn limits(&self) -> Vec<(TaskStatus, Option<String>, usize)> {
+        let global = self.statuses.iter().map(|(s, l)| (s.0, None, *l));
+        let per_context = self.contexts.iter().flat_map(|(context, limits)| {
+            limits
+                .iter()
+                .map(|(s, l)| (s.0, Some(context_marker(context)), *l))
+        });
+        let mut limits: Vec<_> = global.chain(per_context).collect();
+        limits.sort_by_key(|(s, c, _)| (s.to_string(), c.clone()));
+        limits
+    }
+
+    fn applies(status: TaskStatus, context: &Option<String>, task: &Task) -> bool {
+        task.status == status && context.as_ref().is_none_or(|c| task.contexts.contains(c))
+    }
+
+    /// All limits with how many tasks they currently apply to
+    pub fn counts(&self, tasks: &[Task]) -> Vec<LimitCount> {
+        self.limits()
+            .into_iter()
+            .map(|(status, context, limit)| LimitCount {
+                count: tasks
+                    .iter()
+                    .filter(|t| WipLimits::applies(status, &context, t))
+                    .count(),
+                status,
+                context,
+                limit,
+            })
+            .collect()
+    }
+
+    pub fn exceeded(&self, tasks: &[Task]) -> Vec<LimitCount> {
+        self.counts(tasks)
+            .into_iter()
+            .filter(LimitCount::is_exceeded)
+            .collect()
+    }
+
+    /// The limits that would be exceeded if `task` got `status`
+    pub fn exceeded_by(&self, tasks: &[Task], task: &Task, status: TaskStatus) -> Vec<LimitCount> {
+        if task.status == status {
+            return vec![];
+        }
+        let mut changed = task.clone();
+        changed.status = status;
+        let others: Vec<Task> = tasks
+            .iter()
+            .filter(|t| *t != task)
+            .cloned()
+            .chain([changed.clone()])
+            .collect();
+        self.exceeded(&others)
+            .into_iter()
+            .filter(|l| WipLimits::applies(l.status, &l.context, &changed))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> WipLimits {
+        serde_json::from_str(r#"{"wip": 2, "contexts": {"home": {"wip": 1}}}"#).unwrap()
+    }
+
+    #[test]
+    fn a_limit_is_only_exceeded_by_going_over_it() {
+        let tasks = vec![
+            Task::from("- paint door @wip", "house.md"),
+            Task::from("- fix roof @todo", "house.md"),
+            Task::from("- mow lawn @todo #xhome", "garden.md"),
+        ];
+        let limits = limits();
+
+        // The second @wip task is exactly at the limit
+        assert_eq!(
+            limits.exceeded_by(&tasks, &tasks[1], TaskStatus::Wip),
+            vec![]
+        );
+        let mut at_limit = tasks.clone();
+        at_limit[1].status = TaskStatus::Wip;
+        let exceeded = limits.exceeded_by(&at_limit, &at_limit[2], TaskStatus::Wip);
+        assert_eq!(
+            exceeded,
+            vec![LimitCount {
+                status: TaskStatus::Wip,
+                context: None,
+                count: 3,
+                limit: 2,
+            }]
+        );
+        assert_eq!(exceeded[0].to_string(), "3 @wip tasks, the limit is 2");
+    }
+
+    #[test]
+    fn context_limits_apply_to_tasks_with_the_context() {
+        let tasks = vec![
+            Task::from("- fix sink @wip #xhome", "house.md"),
+            Task::from("- fix roof @todo", "house.md"),
+            Task::from("- mow lawn @todo #xhome", "garden.md"),
+        ];
+        let limits = limits();
+        assert_eq!(
+            limits.exceeded_by(&tasks, &tasks[1], TaskStatus::Wip),
+            vec![]
+        );
+        let exceeded = limits.exceeded_by(&tasks, &tasks[2], TaskStatus::Wip);
+        assert_eq!(exceeded.len(), 1);
+        assert_eq!(exceeded[0].context.as_deref(), Some("#xhome"));
+    }
+
+    #[test]
+    fn statuses_without_a_limit_are_never_exceeded() {
+        let tasks: Vec<Task> = (0..5)
+            .map(|i| Task::from(&format!("- task {} @review #xhome", i), "house.md"))
+            .chain([Task::from("- fix roof @todo #xhome", "house.md")])
+            .collect();
+        assert_eq!(
+            limits().exceeded_by(&tasks, &tasks[5], TaskStatus::Review),
+            vec![]
+        );
+        assert_eq!(limits().exceeded(&tasks), vec![]);
+    }
+}
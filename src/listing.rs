@@ -0,0 +1,263 @@
+use crate::model::{Task, TaskStatus};
+use crate::query::DateField;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Due,
+    Start,
+    Priority,
+    Project,
+    Status,
+    Description,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "due" => Ok(Self::Due),
+            "start" => Ok(Self::Start),
+            "priority" => Ok(Self::Priority),
+            "project" => Ok(Self::Project),
+            "status" => Ok(Self::Status),
+            "description" | "desc" => Ok(Self::Description),
+            _ => Err(format!("Unknown sort key: {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Project,
+    Context,
+    Status,
+    Due,
+    Person,
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "project" => Ok(Self::Project),
+            "context" | "ctx" => Ok(Self::Context),
+            "status" => Ok(Self::Status),
+            "due" => Ok(Self::Due),
+            "person" => Ok(Self::Person),
+            _ => Err(format!("Unknown grouping: {s}")),
+        }
+    }
+}
+
+/// Position of a status in [TaskStatus::all], most active first
+fn status_rank(status: &TaskStatus) -> usize {
+    TaskStatus::all()
+        .iter()
+        .position(|s| s == status)
+        .unwrap_or(usize::MAX)
+}
+
+/// Compare options with `None` last
+fn cmp_some_first<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn cmp_by(key: SortKey, a: &Task, b: &Task) -> Ordering {
+    match key {
+        SortKey::Due => cmp_some_first(DateField::Due.of(a), DateField::Due.of(b)),
+        SortKey::Start => cmp_some_first(DateField::Start.of(a), DateField::Start.of(b)),
+        SortKey::Priority => cmp_some_first(a.priority, b.priority),
+        SortKey::Project => a.project.cmp(&b.project),
+        SortKey::Status => status_rank(&a.status).cmp(&status_rank(&b.status)),
+        SortKey::Description => a.title().to_lowercase().cmp(&b.title().to_lowercase()),
+    }
+}
+
+/// Sort by each key in turn. Ties keep the order of the knowledge base, so
+/// without keys tasks are ordered by project and line.
+pub fn sort_tasks(tasks: &mut [Task], keys: &[SortKey]) {
+    tasks.sort_by(|a, b| {
+        keys.iter()
+            .chain(&[SortKey::Project])
+            .fold(Ordering::Equal, |o, k| o.then_with(|| cmp_by(*k, a, b)))
+            .then_with(|| {
+                let line = |t: &Task| t.source.as_ref().map(|s| s.line);
+                line(a).cmp(&line(b))
+            })
+    });
+}
+
+/// Split tasks into named groups, keeping the order of the tasks within each
+/// group. A task is in a group for each of its contexts or people, and tasks
+/// that have none of them go last.
+pub fn group_tasks(tasks: &[Task], group_by: GroupBy) -> Vec<(String, Vec<Task>)> {
+    // Keys sort the groups, the names are shown
+    let mut groups: BTreeMap<(usize, String), (String, Vec<Task>)> = BTreeMap::new();
+    for task in tasks {
+        let mut keys: Vec<((usize, String), String)> = match group_by {
+            GroupBy::Project => vec![((0, task.project.clone()), task.project.clone())],
+            GroupBy::Status => vec![(
+                (status_rank(&task.status), String::new()),
+                task.status.to_string(),
+            )],
+            GroupBy::Due => match DateField::Due.of(task) {
+                Some(due) => vec![((0, due.to_string()), due.to_string())],
+                None => vec![((1, String::new()), "no due date".into())],
+            },
+            GroupBy::Context => match task.contexts.is_empty() {
+                false => task
+                    .contexts
+                    .iter()
+                    .map(|c| ((0, c.clone()), c.clone()))
+                    .collect(),
+                true => vec![((1, String::new()), "no context".into())],
+            },
+            GroupBy::Person => match task.people() {
+                people if people.is_empty() => vec![((1, String::new()), "no person".into())],
+                people => people
+                    .into_iter()
+                    .map(|p| ((0, p.clone()), format!("@{}", p)))
+                    .collect(),
+            },
+        };
+        keys.sort();
+        keys.dedup();
+        for (key, name) in keys {
+            groups
+                .entry(key)
+                .or_insert_with(|| (name, vec![]))
+                .1
+                .push(task.clone());
+        }
+    }
+    groups.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::TaskSource;
+
+    fn task(line: &str, project: &str, number: usize) -> Task {
+        let mut task = Task::from(line, project);
+        task.source = Some(TaskSource {
+            path: format!("{}.md", project).into(),
+            line: number,
+        });
+        task
+    }
+
+    fn titles(tasks: &[Task]) -> Vec<String> {
+        tasks.iter().map(|t| t.title()).collect()
+    }
+
+    #[test]
+    fn sorts_by_each_key_then_project_and_line() {
+        let mut tasks = vec![
+            task("- fix roof @todo @d20240305", "house", 3),
+            task("- paint door @wip @pB", "house", 1),
+            task("- call mom @pA @d20240301", "family", 2),
+            task("- mow lawn @wip @d20240305", "garden", 1),
+            task("- buy paint @wip @d20240305", "house", 2),
+        ];
+
+        sort_tasks(&mut tasks, &[SortKey::Due]);
+        assert_eq!(
+            titles(&tasks),
+            [
+                "call mom",
+                "mow lawn",
+                "buy paint",
+                "fix roof",
+                "paint door"
+            ]
+        );
+        sort_tasks(&mut tasks, &[SortKey::Status, SortKey::Priority]);
+        assert_eq!(
+            titles(&tasks),
+            [
+                "paint door",
+                "mow lawn",
+                "buy paint",
+                "fix roof",
+                "call mom"
+            ]
+        );
+        sort_tasks(&mut tasks, &[SortKey::Description]);
+        assert_eq!(
+            titles(&tasks),
+            [
+                "buy paint",
+                "call mom",
+                "fix roof",
+                "mow lawn",
+                "paint door"
+            ]
+        );
+        sort_tasks(&mut tasks, &[]);
+        assert_eq!(
+            titles(&tasks),
+            [
+                "call mom",
+                "mow lawn",
+                "paint door",
+                "buy paint",
+                "fix roof"
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_by_every_context_and_puts_tasks_without_one_last() {
+        let tasks = vec![
+            task("- call mom #xphone #xhome", "family", 1),
+            task("- fix roof", "house", 1),
+            task("- paint door #xhome", "house", 2),
+        ];
+        let groups: Vec<(String, Vec<String>)> = group_tasks(&tasks, GroupBy::Context)
+            .into_iter()
+            .map(|(name, tasks)| (name, titles(&tasks)))
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                (
+                    "#xhome".to_string(),
+                    vec!["call mom".to_string(), "paint door".into()]
+                ),
+                ("#xphone".into(), vec!["call mom".into()]),
+                ("no context".into(), vec!["fix roof".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_by_status_in_status_order_and_by_due_date() {
+        let tasks = vec![
+            task("- fix roof @todo @d20240305", "house", 1),
+            task("- paint door @wip", "house", 2),
+            task("- call mom @todo @d20240301", "family", 1),
+        ];
+        let names = |group_by| -> Vec<String> {
+            group_tasks(&tasks, group_by)
+                .into_iter()
+                .map(|(name, tasks)| format!("{} {}", name, tasks.len()))
+                .collect()
+        };
+        assert_eq!(names(GroupBy::Status), ["@wip 1", "@todo 2"]);
+        assert_eq!(
+            names(GroupBy::Due),
+            ["2024-03-01 1", "2024-03-05 1", "no due date 1"]
+        );
+    }
+}
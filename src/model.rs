@@ -1,10 +1,16 @@
+use crate::config::{home, ConfigError};
+use crate::limits::WipLimits;
+use crate::outline::LIST_ITEM_RE;
+use crate::view::View;
 use base64::{engine::general_purpose, Engine as _};
+use chrono::NaiveDate;
 use colored::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env::var;
-use std::fs;
+use std::path::PathBuf;
 use std::str::FromStr;
+use uuid::Uuid;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct ServerConfig {
@@ -25,29 +31,29 @@ pub struct ConfigFile {
     pub default_dirs: Option<Vec<std::path::PathBuf>>,
     pub inbox_path: Option<String>,
     pub ignore_files: Option<Vec<String>>,
+    pub include_globs: Option<Vec<String>>,
+    pub exclude_globs: Option<Vec<String>>,
+    pub max_depth: Option<usize>,
+    pub gitignore: Option<bool>,
     pub default_not_context: Option<Vec<String>>,
     pub server: Option<ServerConfig>,
+    pub archive_path: Option<String>,
+    pub wip_limits: Option<WipLimits>,
+    pub views: Option<HashMap<String, View>>,
+    pub ics_path: Option<String>,
+    pub ics_events: Option<bool>,
+    /// Named sets of the settings above, e.g. `work` and `home`, that
+    /// replace the top-level ones when selected
+    pub profiles: Option<HashMap<String, ConfigFile>>,
 }
 
-impl ConfigFile {
-    fn new() -> ConfigFile {
-        return ConfigFile {
-            default_dirs: None,
-            inbox_path: None,
-            ignore_files: None,
-            default_not_context: None,
-            server: None,
-        };
-    }
-
-    pub fn read() -> ConfigFile {
-        let default_config_name = ".gtd.json";
-        let home_path = var("HOME").expect("$HOME not defined");
-        match fs::read_to_string(format!("{}/{}", home_path, default_config_name)) {
-            Err(_) => ConfigFile::new(),
-            Ok(content) => serde_json::from_str(&content).expect("Config was not well formatted"),
-        }
-    }
+/// Where gtd keeps its own state, like the undo journal
+pub fn data_dir() -> Result<PathBuf, ConfigError> {
+    let data_home = match var("XDG_DATA_HOME").ok().filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => home()?.join(".local/share"),
+    };
+    Ok(data_home.join("gtd"))
 }
 
 use regex::Regex;
@@ -59,11 +65,23 @@ pub enum TaskStatus {
     Review,
     Week,
     Month,
+    Done,
 }
 
 impl TaskStatus {
     fn re_status() -> Regex {
-        Regex::new(r"(@todo|@wip|@review|@week|@month)").unwrap()
+        Regex::new(r"(@todo|@wip|@review|@week|@month|@done)").unwrap()
+    }
+    fn re_checked() -> Regex {
+        Regex::new(r"^\s*(\*|-)\s+\[[xX]\]").unwrap()
+    }
+    /// Remove the `[ ]` or `[x]` checkbox, which is part of the status and not
+    /// of the title
+    pub fn remove_checkbox_str(task: &str) -> String {
+        Regex::new(r"^(\s*(\*|-)\s+)\[[ xX]\]\s*")
+            .unwrap()
+            .replace(task, "$1")
+            .to_string()
     }
     pub fn remove_status_str(task: &str) -> String {
         let no_status = TaskStatus::re_status().replace_all(task, "").to_string();
@@ -73,6 +91,9 @@ impl TaskStatus {
             .to_string()
     }
     pub fn classify(task: &str) -> TaskStatus {
+        if TaskStatus::re_checked().is_match(task) {
+            return TaskStatus::Done;
+        }
         let status_str: Option<&str> = TaskStatus::re_status()
             .captures(task)
             .map(|cap| cap.get(0).unwrap().as_str());
@@ -82,14 +103,15 @@ impl TaskStatus {
             .unwrap_or(TaskStatus::NoStatus)
     }
     pub fn all() -> Vec<TaskStatus> {
-        return vec![
+        vec![
             TaskStatus::Wip,
             TaskStatus::Review,
             TaskStatus::Todo,
             TaskStatus::NoStatus,
             TaskStatus::Week,
             TaskStatus::Month,
-        ];
+            TaskStatus::Done,
+        ]
     }
 
     pub fn to_color_str(&self) -> ColoredString {
@@ -100,6 +122,7 @@ impl TaskStatus {
             TaskStatus::Week => self.to_string().red(),
             TaskStatus::Month => self.to_string().red(),
             TaskStatus::Review => self.to_string().yellow(),
+            TaskStatus::Done => self.to_string().dimmed(),
         }
     }
 }
@@ -113,6 +136,7 @@ impl std::fmt::Display for TaskStatus {
             Self::NoStatus => "@noStatus",
             Self::Week => "@week",
             Self::Month => "@month",
+            Self::Done => "@done",
         };
         s.fmt(f)
     }
@@ -128,6 +152,7 @@ impl std::str::FromStr for TaskStatus {
             "@noStatus" => Ok(Self::NoStatus),
             "@week" => Ok(Self::Week),
             "@month" => Ok(Self::Month),
+            "@done" => Ok(Self::Done),
             _ => Err(format!("Unknown status: {s}")),
         }
     }
@@ -157,7 +182,28 @@ impl TaskContext {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug, Hash)]
+/// Priority from `@pA` (highest) to `@pZ`
+struct TaskPriority;
+
+impl TaskPriority {
+    fn re_priority() -> Regex {
+        Regex::new(r"@p([A-Z])\b").unwrap()
+    }
+
+    pub fn extract_priority(task: &str) -> Option<char> {
+        TaskPriority::re_priority()
+            .captures(task)
+            .and_then(|c| c[1].chars().next())
+    }
+
+    pub fn remove_priority_str(task: &str) -> String {
+        TaskPriority::re_priority()
+            .replace_all(task, "")
+            .to_string()
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq, Debug, Hash)]
 pub struct TaskDates {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
@@ -168,14 +214,17 @@ pub struct TaskDates {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub visible: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub completed: Option<String>,
 }
 
 impl TaskDates {
     pub fn re_date() -> Regex {
-        Regex::new(r"(@[d,s,b,v][0-9]{8})").unwrap()
+        Regex::new(r"(@[d,s,b,v,c][0-9]{8})").unwrap()
     }
 
-    fn parse_date(dates: &Vec<String>, c: char) -> Option<String> {
+    fn parse_date(dates: &[String], c: char) -> Option<String> {
         dates
             .iter()
             .find(|s| s.contains(c))
@@ -192,18 +241,38 @@ impl TaskDates {
         let both: Option<String> = TaskDates::parse_date(&dates, 'b');
         let due: Option<String> = both.clone().or(TaskDates::parse_date(&dates, 'd'));
         let visible: Option<String> = both.clone().or(TaskDates::parse_date(&dates, 'v'));
+        let completed: Option<String> = TaskDates::parse_date(&dates, 'c');
 
-        if start.is_none() && due.is_none() && visible.is_none() {
+        if start.is_none() && due.is_none() && visible.is_none() && completed.is_none() {
             None
         } else {
             Some(TaskDates {
                 start,
                 due,
                 visible,
+                completed,
             })
         }
     }
 
+    /// The dates written back as markers, e.g. `@d20240131`
+    pub fn markers(&self) -> Vec<String> {
+        [
+            ('s', &self.start),
+            ('d', &self.due),
+            ('v', &self.visible),
+            ('c', &self.completed),
+        ]
+        .iter()
+        .filter_map(|(c, date)| date.as_ref().map(|d| format!("@{}{}", c, d)))
+        .collect()
+    }
+
+    /// A date as used in the markers, e.g. `20240131`
+    pub fn to_date(date: &str) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(date, "%Y%m%d").ok()
+    }
+
     pub fn remove_date(task: &str) -> String {
         let no_dates = TaskDates::re_date().replace_all(task, "").to_string();
         Regex::new(r"\s+")
@@ -223,20 +292,46 @@ pub struct Task {
     #[serde(default)]
     pub dates: Option<TaskDates>,
     pub starred: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub priority: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub source: Option<TaskSource>,
+}
+
+/// Where in the knowledge base a task was found
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug, Hash)]
+pub struct TaskSource {
+    pub path: PathBuf,
+    /// 1-based line number
+    pub line: usize,
 }
+
+impl std::fmt::Display for TaskSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        format!("{}:{}", self.path.display(), self.line).fmt(f)
+    }
+}
+
 impl Task {
     pub fn re_any() -> Regex {
         // TODO: regex duplicated here.. not very DRY
-        Regex::new(r"(#x[A-Za-z0-9]{1,})|(@[d,s,b,v][0-9]{8})|@todo|@wip|@review|@week|@month")
-            .unwrap()
+        Regex::new(
+            r"(#x[A-Za-z0-9]{1,})|(@[d,s,b,v,c][0-9]{8})|@p[A-Z]\b|@todo|@wip|@review|@week|@month|@done",
+        )
+        .unwrap()
     }
 
     pub fn from(task: &str, project: &str) -> Task {
         let status = TaskStatus::classify(task);
         let contexts = TaskContext::extract_contexts(task);
         let dates = TaskDates::extract_dates(task);
+        let priority = TaskPriority::extract_priority(task);
         let description = TaskDates::remove_date(&TaskContext::remove_context_string(
-            &TaskStatus::remove_status_str(&task),
+            &TaskStatus::remove_status_str(&TaskPriority::remove_priority_str(
+                &TaskStatus::remove_checkbox_str(task),
+            )),
         ));
 
         Task {
@@ -246,11 +341,39 @@ impl Task {
             contexts,
             dates,
             starred: false,
+            priority,
+            source: None,
         }
     }
 
+    /// The description without the list item bullet
+    pub fn title(&self) -> String {
+        LIST_ITEM_RE
+            .replace(&self.description, "")
+            .trim()
+            .to_string()
+    }
+
     pub fn has_noflags(&self) -> bool {
-        self.contexts.is_empty() && self.status == TaskStatus::NoStatus && self.dates.is_none()
+        self.contexts.is_empty()
+            && self.status == TaskStatus::NoStatus
+            && self.dates.is_none()
+            && self.priority.is_none()
+    }
+
+    /// An id that stays the same as long as the project and title do
+    pub fn stable_id(&self) -> Uuid {
+        let name = format!("{}\n{}", self.project, self.title());
+        Uuid::new_v5(&Uuid::NAMESPACE_OID, name.as_bytes())
+    }
+
+    /// People mentioned in the task as `@Name`
+    pub fn people(&self) -> Vec<String> {
+        Regex::new(r"@([A-Z][A-Za-z]+)")
+            .unwrap()
+            .captures_iter(&self.description)
+            .map(|c| c[1].to_string())
+            .collect()
     }
 
     pub fn ctx_line(&self) -> String {
@@ -280,8 +403,24 @@ impl std::fmt::Display for Task {
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct Project {
-    pub file_name: String,
-    pub tasks: HashMap<TaskStatus, Vec<Task>>,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkbox_is_not_part_of_the_title() {
+        let open = Task::from("- [ ] write report #xwork", "work.md");
+        let done = Task::from("- [x] write report #xwork", "work.md");
+        assert_eq!(open.title(), "write report");
+        assert_eq!(done.title(), "write report");
+        assert_eq!(open.status, TaskStatus::NoStatus);
+        assert_eq!(done.status, TaskStatus::Done);
+        assert_eq!(open.stable_id(), done.stable_id());
+    }
+
+    #[test]
+    fn brackets_in_the_title_are_kept() {
+        let task = Task::from("- read [the docs] @todo", "work.md");
+        assert_eq!(task.title(), "read [the docs]");
+    }
 }
@@ -0,0 +1,135 @@
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+pub static LIST_ITEM_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(\*|-)\s+").unwrap());
+
+pub static HEADING_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(#{1,6})\s+(.*?)\s*$").unwrap());
+
+pub fn is_list_item(line: &str) -> bool {
+    LIST_ITEM_RE.is_match(line)
+}
+
+pub fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// The lines belonging to the list item at `start`: the item itself followed by
+/// its nested children and notes, i.e. everything indented deeper than the item.
+pub fn item_block(lines: &[String], start: usize) -> Range<usize> {
+    let indent = indent_of(&lines[start]);
+    let mut end = start + 1;
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if indent_of(line) <= indent {
+            break;
+        }
+        end = i + 1;
+    }
+    start..end
+}
+
+/// Shift a block left so that its first line has no indentation, keeping the
+/// relative indentation of nested lines.
+pub fn dedent(block: &[String]) -> Vec<String> {
+    let indent = block.first().map(|l| indent_of(l)).unwrap_or(0);
+    block
+        .iter()
+        .map(|l| {
+            let strip = indent.min(indent_of(l));
+            l[strip..].to_string()
+        })
+        .collect()
+}
+
+/// All sections titled `heading`, each from its heading line up to the next
+/// heading of the same or a higher level
+pub fn sections(lines: &[String], heading: &str) -> Vec<Range<usize>> {
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, l)| {
+            HEADING_RE
+                .captures(l)
+                .filter(|c| c[2].eq_ignore_ascii_case(heading.trim()))
+                .map(|c| (i, c[1].len()))
+        })
+        .map(|(start, level)| {
+            let end = lines
+                .iter()
+                .enumerate()
+                .skip(start + 1)
+                .find(|(_, l)| {
+                    HEADING_RE
+                        .captures(l)
+                        .map(|c| c[1].len() <= level)
+                        .unwrap_or(false)
+                })
+                .map(|(i, _)| i)
+                .unwrap_or(lines.len());
+            start..end
+        })
+        .collect()
+}
+
+/// Add a list item block at the end of the section under `heading`, creating
+/// the heading at the end of the file if it does not exist yet. Without a
+/// heading the block is added at the end of the file.
+pub fn insert_under_heading(lines: &mut Vec<String>, heading: Option<&str>, block: &[String]) {
+    let block = dedent(block);
+    let Some(heading) = heading else {
+        lines.extend(block);
+        return;
+    };
+
+    match sections(lines, heading).first() {
+        Some(section) => {
+            let mut end = section.end;
+            while end > section.start + 1 && lines[end - 1].trim().is_empty() {
+                end -= 1;
+            }
+            lines.splice(end..end, block);
+        }
+        None => {
+            if lines.last().map(|l| !l.trim().is_empty()).unwrap_or(false) {
+                lines.push("".into());
+            }
+            lines.push(format!("## {}", heading.trim()));
+            lines.extend(block);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn item_block_takes_nested_items_notes_and_blank_lines() {
+        let lines = lines("- a\n  - b\n\n    note\n- c\n");
+        assert_eq!(item_block(&lines, 0), 0..4);
+        assert_eq!(item_block(&lines, 1), 1..4);
+        assert_eq!(item_block(&lines, 4), 4..5);
+    }
+
+    #[test]
+    fn block_is_added_at_the_end_of_its_section() {
+        let mut file = lines("# House\n- a\n\n# Work\n- b");
+        insert_under_heading(&mut file, Some("house"), &lines("  - c\n    note"));
+        assert_eq!(file, lines("# House\n- a\n- c\n  note\n\n# Work\n- b"));
+    }
+
+    #[test]
+    fn missing_heading_is_created() {
+        let mut file = lines("# House\n- a");
+        insert_under_heading(&mut file, Some("Garden"), &lines("- c"));
+        assert_eq!(file, lines("# House\n- a\n\n## Garden\n- c"));
+    }
+}
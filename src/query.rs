@@ -0,0 +1,536 @@
+//! A small query language for filtering tasks, e.g.
+//!
+//! ```text
+//! status:wip,review and (ctx:home or ctx:zz) and due<=+7d and project~"house" and not ctx:private
+//! ```
+//!
+//! Terms are `field op value`:
+//! - `status:wip,review` any of the statuses, `none` for tasks without one
+//! - `ctx:home,zz` (or `context:`) any of the contexts
+//! - `project:house.md` exact project, `project~house` project contains
+//! - `desc~door` description contains
+//! - `due`, `start`, `visible` compared with `<`, `<=`, `>`, `>=`, `:` to
+//!   `today`, `tomorrow`, `yesterday`, `+7d`, `-2w`, `+1m` or `2024-01-31`.
+//!   `due:none` and `due:any` test whether the date is set at all.
+//!
+//! Terms combine with `and`, `or`, `not` and parentheses. Juxtaposed terms are
+//! ANDed, so `status:wip ctx:home` is `status:wip and ctx:home`.
+use crate::model::{Task, TaskDates, TaskStatus};
+use chrono::{Days, Local, Months, NaiveDate};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateField {
+    Start,
+    Due,
+    Visible,
+}
+
+impl DateField {
+    /// The date of this kind on `task`, if it has one
+    pub fn of(&self, task: &Task) -> Option<NaiveDate> {
+        let dates = task.dates.as_ref()?;
+        let date = match self {
+            DateField::Start => &dates.start,
+            DateField::Due => &dates.due,
+            DateField::Visible => &dates.visible,
+        };
+        date.as_deref().and_then(TaskDates::to_date)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+/// A date that may be relative to the day the query is evaluated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateValue {
+    Absolute(NaiveDate),
+    Days(i64),
+    Months(i32),
+}
+
+impl DateValue {
+    /// The date relative to `today`, or `None` if it is out of range
+    pub fn resolve(&self, today: NaiveDate) -> Option<NaiveDate> {
+        match *self {
+            DateValue::Absolute(d) => Some(d),
+            DateValue::Days(n) if n >= 0 => today.checked_add_days(Days::new(n as u64)),
+            DateValue::Days(n) => today.checked_sub_days(Days::new(n.unsigned_abs())),
+            DateValue::Months(n) if n >= 0 => today.checked_add_months(Months::new(n as u32)),
+            DateValue::Months(n) => today.checked_sub_months(Months::new(n.unsigned_abs())),
+        }
+    }
+}
+
+impl FromStr for DateValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "today" => return Ok(DateValue::Days(0)),
+            "tomorrow" => return Ok(DateValue::Days(1)),
+            "yesterday" => return Ok(DateValue::Days(-1)),
+            _ => (),
+        }
+        if let Ok(d) = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .or_else(|_| NaiveDate::parse_from_str(s, "%Y%m%d"))
+        {
+            return Ok(DateValue::Absolute(d));
+        }
+        let invalid = || format!("Invalid date: {s}");
+        let (number, value): (&str, fn(i64) -> Option<DateValue>) =
+            if let Some(n) = s.strip_suffix('d') {
+                (n, |n| Some(DateValue::Days(n)))
+            } else if let Some(n) = s.strip_suffix('w') {
+                (n, |n| n.checked_mul(7).map(DateValue::Days))
+            } else if let Some(n) = s.strip_suffix('m') {
+                (n, |n| i32::try_from(n).ok().map(DateValue::Months))
+            } else {
+                return Err(invalid());
+            };
+        let n: i64 = number.parse().map_err(|_| invalid())?;
+        // Relative dates must also resolve when the query is evaluated
+        value(n)
+            .filter(|v| v.resolve(Local::now().date_naive()).is_some())
+            .ok_or_else(|| format!("Date out of range: {s}"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// Matches every task
+    All,
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Status(Vec<TaskStatus>),
+    /// Contexts including the `#x` prefix
+    Context(Vec<String>),
+    Project(String),
+    ProjectContains(String),
+    DescriptionContains(String),
+    Date(DateField, Cmp, DateValue),
+    HasDate(DateField, bool),
+}
+
+impl Query {
+    pub fn and(self, other: Query) -> Query {
+        match (self, other) {
+            (Query::All, q) | (q, Query::All) => q,
+            (a, b) => Query::And(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn or(self, other: Query) -> Query {
+        Query::Or(Box::new(self), Box::new(other))
+    }
+
+    pub fn negate(self) -> Query {
+        Query::Not(Box::new(self))
+    }
+
+    /// Tasks deferred with a start or visible date after today
+    pub fn deferred() -> Query {
+        Query::Date(DateField::Visible, Cmp::Gt, DateValue::Days(0)).or(Query::Date(
+            DateField::Start,
+            Cmp::Gt,
+            DateValue::Days(0),
+        ))
+    }
+
+    pub fn matches(&self, task: &Task, today: NaiveDate) -> bool {
+        match self {
+            Query::All => true,
+            Query::And(a, b) => a.matches(task, today) && b.matches(task, today),
+            Query::Or(a, b) => a.matches(task, today) || b.matches(task, today),
+            Query::Not(q) => !q.matches(task, today),
+            Query::Status(statuses) => statuses.contains(&task.status),
+            Query::Context(contexts) => task.contexts.iter().any(|c| contexts.contains(c)),
+            Query::Project(p) => task.project.eq_ignore_ascii_case(p),
+            Query::ProjectContains(p) => task.project.to_lowercase().contains(&p.to_lowercase()),
+            Query::DescriptionContains(d) => {
+                task.description.to_lowercase().contains(&d.to_lowercase())
+            }
+            Query::HasDate(field, has) => field.of(task).is_some() == *has,
+            Query::Date(field, cmp, value) => {
+                let (Some(date), Some(value)) = (field.of(task), value.resolve(today)) else {
+                    return false;
+                };
+                match cmp {
+                    Cmp::Lt => date < value,
+                    Cmp::Le => date <= value,
+                    Cmp::Eq => date == value,
+                    Cmp::Ge => date >= value,
+                    Cmp::Gt => date > value,
+                }
+            }
+        }
+    }
+
+    /// Whether the query explicitly asks for tasks with `status`
+    pub fn mentions_status(&self, status: TaskStatus) -> bool {
+        match self {
+            Query::And(a, b) | Query::Or(a, b) => {
+                a.mentions_status(status) || b.mentions_status(status)
+            }
+            Query::Not(q) => q.mentions_status(status),
+            Query::Status(statuses) => statuses.contains(&status),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub message: String,
+    /// Byte offset in the query where the problem is
+    pub position: usize,
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(Cmp, bool),
+    Open,
+    Close,
+}
+
+fn is_op_char(c: char) -> bool {
+    matches!(c, ':' | '~' | '<' | '>' | '=')
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, QueryError> {
+    let mut tokens = vec![];
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(pos, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push((pos, Token::Open));
+            }
+            ')' => {
+                chars.next();
+                tokens.push((pos, Token::Close));
+            }
+            '"' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, c)) => s.push(c),
+                        None => {
+                            return Err(QueryError {
+                                message: "Unterminated string".into(),
+                                position: pos,
+                            })
+                        }
+                    }
+                }
+                tokens.push((pos, Token::Quoted(s)));
+            }
+            c if is_op_char(c) => {
+                let mut op = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !is_op_char(c) {
+                        break;
+                    }
+                    op.push(c);
+                    chars.next();
+                }
+                // The bool marks `~`, a "contains" match
+                let token = match op.as_str() {
+                    ":" | "=" => Token::Op(Cmp::Eq, false),
+                    "~" => Token::Op(Cmp::Eq, true),
+                    "<" => Token::Op(Cmp::Lt, false),
+                    "<=" => Token::Op(Cmp::Le, false),
+                    ">" => Token::Op(Cmp::Gt, false),
+                    ">=" => Token::Op(Cmp::Ge, false),
+                    _ => {
+                        return Err(QueryError {
+                            message: format!("Unknown operator {op}"),
+                            position: pos,
+                        })
+                    }
+                };
+                tokens.push((pos, token));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() || is_op_char(c) || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push((pos, Token::Word(word)));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, t)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map(|(p, _)| *p)
+            .unwrap_or(self.end)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, QueryError> {
+        Err(QueryError {
+            message,
+            position: self.position(),
+        })
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn or(&mut self) -> Result<Query, QueryError> {
+        let mut query = self.and()?;
+        while self.is_keyword("or") {
+            self.next += 1;
+            query = query.or(self.and()?);
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, QueryError> {
+        let mut query = self.unary()?;
+        loop {
+            if self.is_keyword("and") {
+                self.next += 1;
+            } else if self.peek().is_none()
+                || self.is_keyword("or")
+                || self.peek() == Some(&Token::Close)
+            {
+                return Ok(query);
+            }
+            query = Query::And(Box::new(query), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Query, QueryError> {
+        if self.is_keyword("not") {
+            self.next += 1;
+            return Ok(self.unary()?.negate());
+        }
+        match self.peek() {
+            Some(Token::Open) => {
+                self.next += 1;
+                let query = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return self.error("Expected )".into());
+                }
+                self.next += 1;
+                Ok(query)
+            }
+            Some(Token::Word(_)) => self.term(),
+            Some(t) => self.error(format!("Unexpected {:?}", t)),
+            None => self.error("Unexpected end of query".into()),
+        }
+    }
+
+    fn term(&mut self) -> Result<Query, QueryError> {
+        let field_position = self.position();
+        let Some(Token::Word(field)) = self.peek().cloned() else {
+            return self.error("Expected a field".into());
+        };
+        self.next += 1;
+        let Some(Token::Op(cmp, contains)) = self.peek().cloned() else {
+            return self.error(format!("Expected an operator after {field}"));
+        };
+        self.next += 1;
+        let value_position = self.position();
+        let value = match self.peek().cloned() {
+            Some(Token::Word(v)) | Some(Token::Quoted(v)) => v,
+            _ => return self.error(format!("Expected a value for {field}")),
+        };
+        self.next += 1;
+        let value_error = |message: String| QueryError {
+            message,
+            position: value_position,
+        };
+        let only_eq = |query: Query| match (cmp, contains) {
+            (Cmp::Eq, false) => Ok(query),
+            _ => Err(QueryError {
+                message: format!("{field} only supports :"),
+                position: field_position,
+            }),
+        };
+
+        let field = field.to_lowercase();
+        match field.as_str() {
+            "status" => only_eq(Query::Status(
+                value
+                    .split(',')
+                    .map(parse_status)
+                    .collect::<Result<_, _>>()
+                    .map_err(value_error)?,
+            )),
+            "ctx" | "context" => only_eq(Query::Context(
+                value.split(',').map(context_marker).collect(),
+            )),
+            "project" if contains => Ok(Query::ProjectContains(value)),
+            "project" => only_eq(Query::Project(value)),
+            "desc" | "description" if contains => Ok(Query::DescriptionContains(value)),
+            "start" | "due" | "visible" => {
+                let field = match field.as_str() {
+                    "start" => DateField::Start,
+                    "due" => DateField::Due,
+                    _ => DateField::Visible,
+                };
+                match (value.to_lowercase().as_str(), cmp, contains) {
+                    (_, _, true) => Err(value_error("Dates do not support ~".into())),
+                    ("none", Cmp::Eq, _) => Ok(Query::HasDate(field, false)),
+                    ("any", Cmp::Eq, _) => Ok(Query::HasDate(field, true)),
+                    _ => Ok(Query::Date(field, cmp, value.parse().map_err(value_error)?)),
+                }
+            }
+            _ => Err(QueryError {
+                message: format!("Unknown field {field}"),
+                position: field_position,
+            }),
+        }
+    }
+}
+
+/// A status without the `@`, e.g. `wip`, or `none` for tasks without a status
+pub fn parse_status(s: &str) -> Result<TaskStatus, String> {
+    match s.trim().to_lowercase().as_str() {
+        "none" | "nostatus" => Ok(TaskStatus::NoStatus),
+        s => TaskStatus::from_str(&format!("@{s}")),
+    }
+}
+
+/// A context with or without the `#x` prefix as it appears in the tasks
+pub fn context_marker(s: &str) -> String {
+    let s = s.trim();
+    match s.starts_with("#x") {
+        true => s.to_string(),
+        false => format!("#x{}", s),
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            next: 0,
+            end: s.len(),
+        };
+        if parser.peek().is_none() {
+            return Ok(Query::All);
+        }
+        let query = parser.or()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some(t) => parser.error(format!("Unexpected {:?}", t)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn parses_relative_and_absolute_dates() {
+        assert_eq!("today".parse(), Ok(DateValue::Days(0)));
+        assert_eq!("+7d".parse(), Ok(DateValue::Days(7)));
+        assert_eq!("-2w".parse(), Ok(DateValue::Days(-14)));
+        assert_eq!("+1m".parse(), Ok(DateValue::Months(1)));
+        assert_eq!(
+            "2024-01-31".parse(),
+            Ok(DateValue::Absolute(date("2024-01-31")))
+        );
+        assert_eq!(
+            "20240131".parse(),
+            Ok(DateValue::Absolute(date("2024-01-31")))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_dates_without_panicking() {
+        for s in [
+            "ü",
+            "1ü",
+            "d",
+            "+",
+            "",
+            "+99999999999d",
+            "9223372036854775807w",
+            "+3000000000m",
+        ] {
+            assert!(s.parse::<DateValue>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn resolve_is_none_out_of_range() {
+        assert_eq!(DateValue::Days(i64::MAX).resolve(date("2024-01-31")), None);
+        assert_eq!(
+            DateValue::Months(i32::MIN).resolve(date("2024-01-31")),
+            None
+        );
+        assert_eq!(
+            DateValue::Months(1).resolve(date("2024-01-31")),
+            Some(date("2024-02-29"))
+        );
+    }
+
+    #[test]
+    fn date_errors_point_at_the_value() {
+        let err = "due<ü".parse::<Query>().unwrap_err();
+        assert_eq!(err.position, 4);
+        assert!("due<+99999999999d".parse::<Query>().is_err());
+    }
+
+    #[test]
+    fn parses_boolean_combinations() {
+        let query: Query = "status:wip ctx:home or not due:none".parse().unwrap();
+        let wip = Query::Status(vec![TaskStatus::Wip]);
+        let home = Query::Context(vec!["#xhome".into()]);
+        let dated = Query::HasDate(DateField::Due, false).negate();
+        assert_eq!(query, Query::And(Box::new(wip), Box::new(home)).or(dated));
+    }
+}
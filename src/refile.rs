@@ -0,0 +1,206 @@
+use crate::edit::{EditError, EditFile};
+use crate::journal;
+use crate::model::Task;
+use crate::outline::{insert_under_heading, item_block};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Whether both paths are the same existing file
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Add a list item block to a project file, see [insert_under_heading]
+pub fn insert_block(
+    project: &Path,
+    heading: Option<&str>,
+    block: &[String],
+) -> Result<(), EditError> {
+    let mut file = EditFile::open(project)?;
+    insert_under_heading(&mut file.lines, heading, block);
+    let command = format!("add '{}' to {}", block[0].trim(), project.display());
+    journal::commit(&command, &mut [&mut file])
+}
+
+/// Cut the list item of `task`, with its nested children and notes, out of its
+/// project file and add it under `heading` in the `target` project file.
+pub fn move_task(task: &Task, target: &Path, heading: Option<&str>) -> Result<(), EditError> {
+    let source_path = &task
+        .source
+        .as_ref()
+        .ok_or(EditError::NoSource(task.title()))?
+        .path;
+    let mut source = EditFile::open(source_path)?;
+    let start = source.task_line(task)?;
+    let block: Vec<String> = source
+        .lines
+        .drain(item_block(&source.lines, start))
+        .collect();
+
+    let command = format!("move '{}' to {}", task.title(), target.display());
+    if same_file(source_path, target) {
+        insert_under_heading(&mut source.lines, heading, &block);
+        return journal::commit(&command, &mut [&mut source]);
+    }
+    let mut target = EditFile::open(target)?;
+    insert_under_heading(&mut target.lines, heading, &block);
+    journal::commit(&command, &mut [&mut target, &mut source])
+}
+
+/// A list item read from another task manager, with the project it was in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Imported {
+    pub project: Option<String>,
+    pub item: String,
+}
+
+/// The text without its `+project` words, and the projects, as an item filed
+/// in the inbox by [file_imported] has them
+pub fn split_projects(text: &str) -> (String, Vec<String>) {
+    let (projects, words): (Vec<&str>, Vec<&str>) = text
+        .split_whitespace()
+        .partition(|w| w.len() > 1 && w.starts_with('+'));
+    let projects = projects.iter().map(|p| p[1..].to_string()).collect();
+    (words.join(" "), projects)
+}
+
+/// The file of a project, e.g. `home improvement.md` for `home-improvement`
+fn project_file<'a>(project: &str, files: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    let name = |s: &str| s.split_whitespace().collect::<Vec<_>>().join("-");
+    files.iter().find(|f| {
+        f.file_stem()
+            .is_some_and(|s| name(&s.to_string_lossy()) == name(project))
+    })
+}
+
+/// Add imported items to the files of their projects. Items without a project
+/// file go to the inbox, with their project as `+project` for when they are
+/// refiled. Returns how many items each file got.
+pub fn file_imported(
+    items: &[Imported],
+    inbox_path: &Path,
+    project_files: &[PathBuf],
+    command: &str,
+) -> Result<Vec<(PathBuf, usize)>, EditError> {
+    let mut files = vec![(EditFile::open_or_create(inbox_path)?, 0)];
+    for imported in items {
+        let target = imported
+            .project
+            .as_deref()
+            .and_then(|p| project_file(p, project_files));
+        let i = match target {
+            None => 0,
+            Some(path) if path == inbox_path || same_file(path, inbox_path) => 0,
+            Some(path) => match files.iter().position(|(f, _)| f.path == *path) {
+                Some(i) => i,
+                None => {
+                    files.push((EditFile::open(path)?, 0));
+                    files.len() - 1
+                }
+            },
+        };
+        let item = match (target, &imported.project) {
+            (None, Some(project)) => format!("{} +{}", imported.item, project),
+            _ => imported.item.clone(),
+        };
+        insert_under_heading(&mut files[i].0.lines, None, &[item]);
+        files[i].1 += 1;
+    }
+    let mut edited: Vec<&mut EditFile> = files.iter_mut().map(|(f, _)| f).collect();
+    journal::commit(command, &mut edited)?;
+    Ok(files
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(f, count)| (f.path, count))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawl::read_tasks;
+    use crate::testing::{temp_dir, use_test_data_dir};
+
+    fn task(path: &Path, title: &str) -> Task {
+        read_tasks(path)
+            .into_iter()
+            .find(|t| t.title() == title)
+            .unwrap()
+    }
+
+    #[test]
+    fn moves_a_nested_block_with_its_notes() {
+        use_test_data_dir();
+        let dir = temp_dir("refile-move");
+        let (house, garden) = (dir.join("house.md"), dir.join("garden.md"));
+        fs::write(
+            &house,
+            "# House\n- paint door @wip #xhome\n  - buy paint\n    note\n- fix roof @todo\n",
+        )
+        .unwrap();
+        fs::write(&garden, "# Garden\n- mow lawn @todo\n").unwrap();
+
+        move_task(&task(&house, "paint door"), &garden, Some("Garden")).unwrap();
+        assert_eq!(
+            fs::read_to_string(&house).unwrap(),
+            "# House\n- fix roof @todo\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&garden).unwrap(),
+            "# Garden\n- mow lawn @todo\n- paint door @wip #xhome\n  - buy paint\n    note\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn moves_within_one_file_to_a_new_heading() {
+        use_test_data_dir();
+        let dir = temp_dir("refile-same-file");
+        let house = dir.join("house.md");
+        fs::write(
+            &house,
+            "# House\n  - paint door @wip\n    note\n- fix roof @todo\n",
+        )
+        .unwrap();
+
+        move_task(&task(&house, "paint door"), &house, Some("Later")).unwrap();
+        assert_eq!(
+            fs::read_to_string(&house).unwrap(),
+            "# House\n- fix roof @todo\n\n## Later\n- paint door @wip\n  note\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files_imported_items_under_their_project() {
+        use_test_data_dir();
+        let dir = temp_dir("refile-import");
+        let (house, inbox) = (dir.join("home improvement.md"), dir.join("inbox.md"));
+        fs::write(&house, "- paint door @wip\n").unwrap();
+        let items = [
+            ("home-improvement", "- fix roof @todo"),
+            ("garden", "- mow lawn"),
+            ("inbox", "- call mom"),
+        ]
+        .map(|(project, item)| Imported {
+            project: Some(project.into()),
+            item: item.into(),
+        });
+        let files = [house.clone(), inbox.clone()];
+
+        let counts = file_imported(&items, &inbox, &files, "import").unwrap();
+        assert_eq!(counts, [(inbox.clone(), 2), (house.clone(), 1)]);
+        assert_eq!(
+            fs::read_to_string(&house).unwrap(),
+            "- paint door @wip\n- fix roof @todo\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&inbox).unwrap(),
+            "- mow lawn +garden\n- call mom\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
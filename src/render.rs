@@ -0,0 +1,309 @@
+use crate::model::{Task, TaskDates};
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Description,
+    Project,
+    Status,
+    Contexts,
+    Dates,
+    /// Where the task is, as `path:line`
+    Source,
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "description" | "desc" => Ok(Self::Description),
+            "project" => Ok(Self::Project),
+            "status" => Ok(Self::Status),
+            "contexts" | "context" | "ctx" => Ok(Self::Contexts),
+            "dates" => Ok(Self::Dates),
+            "path:line" | "path" | "source" => Ok(Self::Source),
+            _ => Err(format!("Unknown column: {s}")),
+        }
+    }
+}
+
+impl Column {
+    pub fn defaults() -> Vec<Column> {
+        vec![
+            Column::Status,
+            Column::Description,
+            Column::Project,
+            Column::Contexts,
+            Column::Dates,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Description => "description",
+            Column::Project => "project",
+            Column::Status => "status",
+            Column::Contexts => "contexts",
+            Column::Dates => "dates",
+            Column::Source => "path:line",
+        }
+    }
+
+    /// The dates of a task as e.g. `start:2024-01-20 due:2024-01-31`
+    fn dates(task: &Task) -> String {
+        let Some(dates) = &task.dates else {
+            return String::new();
+        };
+        [
+            ("start", &dates.start),
+            ("due", &dates.due),
+            ("visible", &dates.visible),
+            ("completed", &dates.completed),
+        ]
+        .iter()
+        .filter_map(|(name, date)| {
+            let date = date.as_deref().and_then(TaskDates::to_date)?;
+            Some(format!("{}:{}", name, date))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+
+    pub fn value(&self, task: &Task) -> String {
+        match self {
+            Column::Description => task.title(),
+            Column::Project => task.project.clone(),
+            Column::Status => task.status.to_string(),
+            Column::Contexts => task.contexts.join(" "),
+            Column::Dates => Column::dates(task),
+            Column::Source => task
+                .source
+                .as_ref()
+                .map(|s| s.to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn json(&self, task: &Task) -> Value {
+        match self {
+            Column::Contexts => Value::from(task.contexts.clone()),
+            Column::Dates => serde_json::to_value(&task.dates).unwrap(),
+            _ => Value::from(self.value(task)),
+        }
+    }
+}
+
+/// Turns a list of tasks into text with one row per task
+pub trait Renderer {
+    fn render(&self, tasks: &[Task], columns: &[Column]) -> String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Csv,
+    Markdown,
+    Html,
+    Ndjson,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "csv" => Ok(Self::Csv),
+            "md" | "markdown" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            "ndjson" | "jsonl" => Ok(Self::Ndjson),
+            _ => Err(format!("Unknown format: {s}")),
+        }
+    }
+}
+
+impl Format {
+    pub fn renderer(&self) -> Box<dyn Renderer> {
+        match self {
+            Format::Table => Box::new(Table),
+            Format::Csv => Box::new(Csv),
+            Format::Markdown => Box::new(Markdown),
+            Format::Html => Box::new(Html),
+            Format::Ndjson => Box::new(Ndjson),
+        }
+    }
+}
+
+fn rows(tasks: &[Task], columns: &[Column]) -> Vec<Vec<String>> {
+    tasks
+        .iter()
+        .map(|t| columns.iter().map(|c| c.value(t)).collect())
+        .collect()
+}
+
+fn header(columns: &[Column]) -> Vec<String> {
+    columns.iter().map(|c| c.name().to_string()).collect()
+}
+
+/// Plain text columns padded to line up
+pub struct Table;
+
+impl Renderer for Table {
+    fn render(&self, tasks: &[Task], columns: &[Column]) -> String {
+        let rows: Vec<Vec<String>> = [header(columns)]
+            .into_iter()
+            .chain(rows(tasks, columns))
+            .collect();
+        let widths: Vec<usize> = (0..columns.len())
+            .map(|i| rows.iter().map(|r| r[i].chars().count()).max().unwrap_or(0))
+            .collect();
+        rows.iter()
+            .map(|row| {
+                let cells: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                    .collect();
+                cells.join("  ").trim_end().to_string() + "\n"
+            })
+            .collect()
+    }
+}
+
+pub struct Csv;
+
+impl Csv {
+    fn quote(cell: &str) -> String {
+        match cell.contains([',', '"', '\n', '\r']) {
+            true => format!("\"{}\"", cell.replace('"', "\"\"")),
+            false => cell.to_string(),
+        }
+    }
+}
+
+impl Renderer for Csv {
+    fn render(&self, tasks: &[Task], columns: &[Column]) -> String {
+        [header(columns)]
+            .into_iter()
+            .chain(rows(tasks, columns))
+            .map(|row| {
+                let cells: Vec<String> = row.iter().map(|c| Csv::quote(c)).collect();
+                cells.join(",") + "\r\n"
+            })
+            .collect()
+    }
+}
+
+pub struct Markdown;
+
+impl Renderer for Markdown {
+    fn render(&self, tasks: &[Task], columns: &[Column]) -> String {
+        let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+        let mut md = line(header(columns));
+        md.push_str(&line(columns.iter().map(|_| "---".to_string()).collect()));
+        for row in rows(tasks, columns) {
+            md.push_str(&line(row.iter().map(|c| c.replace('|', "\\|")).collect()));
+        }
+        md
+    }
+}
+
+pub struct Html;
+
+impl Html {
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+}
+
+impl Renderer for Html {
+    fn render(&self, tasks: &[Task], columns: &[Column]) -> String {
+        let row = |tag: &str, cells: Vec<String>| {
+            let cells: String = cells
+                .iter()
+                .map(|c| format!("<{tag}>{}</{tag}>", Html::escape(c)))
+                .collect();
+            format!("  <tr>{}</tr>\n", cells)
+        };
+        let mut html = String::from("<table>\n");
+        html.push_str(&row("th", header(columns)));
+        for cells in rows(tasks, columns) {
+            html.push_str(&row("td", cells));
+        }
+        html.push_str("</table>\n");
+        html
+    }
+}
+
+/// One JSON object per line
+pub struct Ndjson;
+
+impl Renderer for Ndjson {
+    fn render(&self, tasks: &[Task], columns: &[Column]) -> String {
+        tasks
+            .iter()
+            .map(|task| {
+                let object: Map<String, Value> = columns
+                    .iter()
+                    .map(|c| (c.name().to_string(), c.json(task)))
+                    .collect();
+                Value::Object(object).to_string() + "\n"
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks() -> Vec<Task> {
+        vec![
+            Task::from(r#"- fix "the" roof, then paint @todo"#, "house.md"),
+            Task::from("- compare a|b & <c> @wip #xhome", "work.md"),
+        ]
+    }
+
+    const COLUMNS: &[Column] = &[Column::Description, Column::Contexts];
+
+    #[test]
+    fn csv_quotes_cells_with_separators_quotes_and_newlines() {
+        assert_eq!(
+            Csv.render(&tasks(), COLUMNS),
+            "description,contexts\r\n\
+             \"fix \"\"the\"\" roof, then paint\",\r\n\
+             compare a|b & <c>,#xhome\r\n"
+        );
+        assert_eq!(Csv::quote("two\nlines"), "\"two\nlines\"");
+        assert_eq!(Csv::quote("cr\r"), "\"cr\r\"");
+        assert_eq!(Csv::quote("plain text"), "plain text");
+    }
+
+    #[test]
+    fn markdown_escapes_pipes() {
+        assert_eq!(
+            Markdown.render(&tasks(), COLUMNS),
+            "| description | contexts |\n\
+             | --- | --- |\n\
+             | fix \"the\" roof, then paint |  |\n\
+             | compare a\\|b & <c> | #xhome |\n"
+        );
+    }
+
+    #[test]
+    fn html_escapes_markup() {
+        assert_eq!(
+            Html.render(&tasks(), COLUMNS),
+            "<table>\n  \
+             <tr><th>description</th><th>contexts</th></tr>\n  \
+             <tr><td>fix &quot;the&quot; roof, then paint</td><td></td></tr>\n  \
+             <tr><td>compare a|b &amp; &lt;c&gt;</td><td>#xhome</td></tr>\n\
+             </table>\n"
+        );
+    }
+}
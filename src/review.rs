@@ -0,0 +1,160 @@
+use crate::crawl::{modified_on, project_name};
+use crate::edit::EditFile;
+use crate::model::{Task, TaskStatus};
+use crate::outline::{indent_of, is_list_item};
+use crate::query::DateField;
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// What needs attention in a GTD weekly review
+#[derive(Debug, Clone)]
+pub struct Review {
+    pub date: NaiveDate,
+    /// Items waiting in the inbox, `None` without an inbox
+    pub inbox_count: Option<usize>,
+    /// Projects without an open `@todo` or `@wip` task, also those with no
+    /// tasks at all
+    pub no_next_action: Vec<String>,
+    /// `@week` and `@month` tasks to plan again
+    pub to_replan: Vec<Task>,
+    /// `@review` tasks whose file has not changed in a while
+    pub stale_review: Vec<Task>,
+    pub overdue: Vec<Task>,
+    pub no_context: Vec<Task>,
+}
+
+/// The number of items in the inbox file
+pub fn inbox_count(inbox_path: &Path) -> Option<usize> {
+    let inbox = EditFile::open(inbox_path).ok()?;
+    Some(
+        inbox
+            .lines
+            .iter()
+            .filter(|l| is_list_item(l) && indent_of(l) == 0)
+            .count(),
+    )
+}
+
+impl Review {
+    pub fn new(
+        tasks: &[Task],
+        project_files: &[PathBuf],
+        inbox_path: Option<&Path>,
+        stale_days: i64,
+        today: NaiveDate,
+    ) -> Review {
+        let open: Vec<&Task> = tasks
+            .iter()
+            .filter(|t| t.status != TaskStatus::Done)
+            .collect();
+        let select = |f: &dyn Fn(&Task) -> bool| -> Vec<Task> {
+            open.iter().filter(|t| f(t)).map(|t| (*t).clone()).collect()
+        };
+
+        let mut has_next_action: BTreeMap<String, bool> = project_files
+            .iter()
+            .map(|p| (project_name(p), false))
+            .collect();
+        for task in &open {
+            let next = matches!(task.status, TaskStatus::Todo | TaskStatus::Wip);
+            *has_next_action.entry(task.project.clone()).or_default() |= next;
+        }
+
+        Review {
+            date: today,
+            inbox_count: inbox_path.and_then(inbox_count),
+            no_next_action: has_next_action
+                .into_iter()
+                .filter(|(_, next)| !next)
+                .map(|(p, _)| p)
+                .collect(),
+            to_replan: select(&|t| matches!(t.status, TaskStatus::Week | TaskStatus::Month)),
+            stale_review: select(&|t| {
+                t.status == TaskStatus::Review
+                    && t.source
+                        .as_ref()
+                        .and_then(|s| modified_on(&s.path))
+                        .map(|d| (today - d).num_days() > stale_days)
+                        .unwrap_or(false)
+            }),
+            overdue: select(&|t| DateField::Due.of(t).map(|d| d < today).unwrap_or(false)),
+            no_context: select(&|t| t.contexts.is_empty()),
+        }
+    }
+
+    pub fn task_sections(&self) -> Vec<(&'static str, &Vec<Task>)> {
+        vec![
+            ("Overdue", &self.overdue),
+            ("Stale @review", &self.stale_review),
+            ("@week and @month to plan again", &self.to_replan),
+            ("Without a context", &self.no_context),
+        ]
+    }
+
+    /// The report as Markdown to paste into a journal
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!("# Weekly review {}\n\n", self.date);
+        if let Some(count) = self.inbox_count {
+            md.push_str(&format!("## Inbox\n{} items to process\n\n", count));
+        }
+        md.push_str("## Projects without a next action\n");
+        for project in &self.no_next_action {
+            md.push_str(&format!("- {}\n", project));
+        }
+        md.push('\n');
+        for (title, tasks) in self.task_sections() {
+            md.push_str(&format!("## {}\n", title));
+            for task in tasks {
+                md.push_str(&format!("- {} ({})\n", task.title(), task.project));
+            }
+            md.push('\n');
+        }
+        md
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawl::read_tasks;
+    use crate::testing::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn finds_what_needs_attention() {
+        let dir = temp_dir("review");
+        let files = [
+            (
+                "house.md",
+                "- fix roof @week\n- paint door @review #xhome\n",
+            ),
+            (
+                "family.md",
+                "- call mom @todo @d20240220 #xphone\n- visit @done\n",
+            ),
+            ("garden.md", "Some notes\n- not a task\n"),
+        ];
+        let paths: Vec<PathBuf> = files
+            .iter()
+            .map(|(name, content)| {
+                fs::write(dir.join(name), content).unwrap();
+                dir.join(name)
+            })
+            .collect();
+        let inbox = dir.join("inbox.md");
+        fs::write(&inbox, "- buy milk\n  - oat\n- call plumber\n").unwrap();
+        let tasks: Vec<Task> = paths.iter().flat_map(|p| read_tasks(p)).collect();
+        let today = NaiveDate::from_ymd_opt(2099, 1, 1).unwrap();
+
+        let review = Review::new(&tasks, &paths, Some(&inbox), 7, today);
+        let titles = |tasks: &[Task]| -> Vec<String> { tasks.iter().map(|t| t.title()).collect() };
+        assert_eq!(review.inbox_count, Some(2));
+        assert_eq!(review.no_next_action, ["garden.md", "house.md"]);
+        assert_eq!(titles(&review.to_replan), ["fix roof"]);
+        assert_eq!(titles(&review.stale_review), ["paint door"]);
+        assert_eq!(titles(&review.overdue), ["call mom"]);
+        assert_eq!(titles(&review.no_context), ["fix roof"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,310 @@
+use crate::config::ConfigError;
+use crate::edit::EditError;
+use crate::model::{data_dir, Task, TaskDates, TaskStatus};
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A task as it was at the time of a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotTask {
+    /// See [Task::stable_id]
+    pub id: Uuid,
+    pub status: TaskStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub dates: Option<TaskDates>,
+}
+
+/// The status and dates of every task in the knowledge base at one crawl
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub time: String,
+    pub tasks: Vec<SnapshotTask>,
+}
+
+impl Snapshot {
+    pub fn new(tasks: &[Task]) -> Snapshot {
+        let mut tasks: Vec<SnapshotTask> = tasks
+            .iter()
+            .map(|t| SnapshotTask {
+                id: t.stable_id(),
+                status: t.status,
+                dates: t.dates.clone(),
+            })
+            .collect();
+        tasks.sort_by_key(|t| t.id);
+        Snapshot {
+            time: Local::now().format(TIME_FORMAT).to_string(),
+            tasks,
+        }
+    }
+
+    fn time(&self) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(&self.time, TIME_FORMAT).ok()
+    }
+
+    fn statuses(&self) -> HashMap<Uuid, TaskStatus> {
+        self.tasks.iter().map(|t| (t.id, t.status)).collect()
+    }
+}
+
+/// Each knowledge base keeps its own snapshots, see [ConfigFile::state_key]
+///
+/// [ConfigFile::state_key]: crate::model::ConfigFile::state_key
+pub fn snapshots_path(key: Option<&str>) -> Result<PathBuf, ConfigError> {
+    Ok(match key {
+        Some(key) => data_dir()?.join(format!("snapshots-{}.jsonl", key)),
+        None => data_dir()?.join("snapshots.jsonl"),
+    })
+}
+
+pub fn read_snapshots(path: &Path) -> Vec<Snapshot> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
+
+/// The last line of the file and where it starts, reading only as much of
+/// the end of the file as it takes
+fn last_line(file: &mut File) -> io::Result<Option<(u64, String)>> {
+    const CHUNK: u64 = 64 * 1024;
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut tail: Vec<u8> = vec![];
+    let mut start = len;
+    loop {
+        // The newline that ends the last line does not count
+        let end = tail.len().saturating_sub(1);
+        if let Some(i) = tail[..end].iter().rposition(|b| *b == b'\n') {
+            tail.drain(..=i);
+            start += i as u64 + 1;
+            break;
+        }
+        if start == 0 {
+            break;
+        }
+        let from = start.saturating_sub(CHUNK);
+        let mut chunk = vec![0; (start - from) as usize];
+        file.seek(SeekFrom::Start(from))?;
+        file.read_exact(&mut chunk)?;
+        chunk.append(&mut tail);
+        tail = chunk;
+        start = from;
+    }
+    Ok(match tail.is_empty() {
+        true => None,
+        false => Some((start, String::from_utf8_lossy(&tail).trim_end().to_string())),
+    })
+}
+
+/// Add a snapshot of the tasks to the history, unless nothing changed since
+/// the last one. A snapshot replaces the last one when that is from the same
+/// day, so the history keeps one snapshot per day.
+pub fn append(tasks: &[Task], key: Option<&str>) -> Result<(), EditError> {
+    let path = snapshots_path(key).map_err(EditError::NoDataDir)?;
+    let io_err = |e| EditError::Io(path.clone(), e);
+    let snapshot = Snapshot::new(tasks);
+    fs::create_dir_all(path.parent().unwrap()).map_err(io_err)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(&path)
+        .map_err(io_err)?;
+    let last = last_line(&mut file)
+        .map_err(io_err)?
+        .and_then(|(start, line)| {
+            serde_json::from_str::<Snapshot>(&line)
+                .ok()
+                .map(|l| (start, l))
+        });
+    if let Some((start, last)) = last {
+        if last.tasks == snapshot.tasks {
+            return Ok(());
+        }
+        let day = |s: &Snapshot| s.time().map(|t| t.date());
+        if day(&last).is_some() && day(&last) == day(&snapshot) {
+            file.set_len(start).map_err(io_err)?;
+        }
+    }
+    file.seek(SeekFrom::End(0))
+        .and_then(|_| writeln!(file, "{}", serde_json::to_string(&snapshot).unwrap()))
+        .map_err(io_err)
+}
+
+/// What happened in one week, starting on Monday
+#[derive(Debug, Clone, Serialize)]
+pub struct Week {
+    pub start: String,
+    /// Tasks seen for the first time
+    pub created: usize,
+    /// Tasks that became done
+    pub completed: usize,
+    /// Tasks in `@wip` at the last snapshot of the week
+    pub wip: usize,
+    /// Tasks in `@review` at the last snapshot of the week
+    pub review: usize,
+}
+
+/// How long tasks have stayed in a status, counted between snapshots
+#[derive(Debug, Clone, Serialize)]
+pub struct Dwell {
+    pub status: TaskStatus,
+    pub tasks: usize,
+    pub mean_days: f64,
+    pub median_days: f64,
+    pub max_days: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Trends {
+    pub weeks: Vec<Week>,
+    pub dwell: Vec<Dwell>,
+}
+
+fn monday(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+fn mean(days: &[f64]) -> f64 {
+    days.iter().sum::<f64>() / days.len() as f64
+}
+
+fn dwell(status: TaskStatus, snapshots: &[(NaiveDateTime, &Snapshot)]) -> Dwell {
+    let mut days: HashMap<Uuid, f64> = HashMap::new();
+    for pair in snapshots.windows(2) {
+        let ((from, snapshot), (to, _)) = (pair[0], pair[1]);
+        let elapsed = (to - from).num_seconds() as f64 / 86400.0;
+        for task in snapshot.tasks.iter().filter(|t| t.status == status) {
+            *days.entry(task.id).or_default() += elapsed;
+        }
+    }
+    let mut days: Vec<f64> = days.into_values().collect();
+    days.sort_by(f64::total_cmp);
+    let (mean_days, median_days) = match days.len() {
+        // Not the sum of no days, which is -0.0
+        0 => (0.0, 0.0),
+        n if n % 2 == 0 => (mean(&days), (days[n / 2 - 1] + days[n / 2]) / 2.0),
+        n => (mean(&days), days[n / 2]),
+    };
+    Dwell {
+        status,
+        tasks: days.len(),
+        mean_days,
+        median_days,
+        max_days: days.last().copied().unwrap_or(0.0),
+    }
+}
+
+impl Trends {
+    /// The trends of the last `weeks` weeks. Tasks already there at the first
+    /// snapshot are not counted as created or completed.
+    pub fn new(snapshots: &[Snapshot], weeks: usize) -> Trends {
+        let snapshots: Vec<(NaiveDateTime, &Snapshot)> = snapshots
+            .iter()
+            .filter_map(|s| Some((s.time()?, s)))
+            .collect();
+
+        let mut by_week: BTreeMap<NaiveDate, Week> = BTreeMap::new();
+        let mut seen: HashSet<Uuid> = HashSet::new();
+        let mut previous: HashMap<Uuid, TaskStatus> = HashMap::new();
+        for (i, (time, snapshot)) in snapshots.iter().enumerate() {
+            let start = monday(time.date());
+            let week = by_week.entry(start).or_insert_with(|| Week {
+                start: start.to_string(),
+                created: 0,
+                completed: 0,
+                wip: 0,
+                review: 0,
+            });
+            for task in &snapshot.tasks {
+                if seen.insert(task.id) && i > 0 {
+                    week.created += 1;
+                }
+                let was_done = previous.get(&task.id) == Some(&TaskStatus::Done);
+                if task.status == TaskStatus::Done && !was_done && i > 0 {
+                    week.completed += 1;
+                }
+            }
+            let count = |status| snapshot.tasks.iter().filter(|t| t.status == status).count();
+            week.wip = count(TaskStatus::Wip);
+            week.review = count(TaskStatus::Review);
+            previous = snapshot.statuses();
+        }
+
+        // Weeks without a snapshot keep the WIP of the week before
+        let mut all_weeks: Vec<Week> = vec![];
+        if let (Some(first), Some(last)) = (by_week.keys().next(), by_week.keys().last()) {
+            let mut start = *first;
+            while start <= *last {
+                let week = by_week.get(&start).cloned().unwrap_or_else(|| {
+                    let before = all_weeks.last().unwrap();
+                    Week {
+                        start: start.to_string(),
+                        created: 0,
+                        completed: 0,
+                        wip: before.wip,
+                        review: before.review,
+                    }
+                });
+                all_weeks.push(week);
+                start += Duration::weeks(1);
+            }
+        }
+        let skip = all_weeks.len().saturating_sub(weeks);
+
+        Trends {
+            weeks: all_weeks.split_off(skip),
+            dwell: vec![
+                dwell(TaskStatus::Wip, &snapshots),
+                dwell(TaskStatus::Review, &snapshots),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_line_reads_across_chunks() {
+        let path = std::env::temp_dir().join(format!("gtd-snapshot-test-{}", std::process::id()));
+        let long = "x".repeat(100_000);
+        fs::write(&path, format!("first\n{}\nlast\n", long)).unwrap();
+        let mut file = File::open(&path).unwrap();
+        assert_eq!(
+            last_line(&mut file).unwrap(),
+            Some((100_007, "last".to_string()))
+        );
+        fs::write(&path, format!("{}\n", long)).unwrap();
+        let mut file = File::open(&path).unwrap();
+        assert_eq!(last_line(&mut file).unwrap(), Some((0, long)));
+        fs::write(&path, "").unwrap();
+        let mut file = File::open(&path).unwrap();
+        assert_eq!(last_line(&mut file).unwrap(), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn dwell_without_tasks_is_zero() {
+        let snapshot = Snapshot {
+            time: "2024-10-01 12:00:00".into(),
+            tasks: vec![],
+        };
+        let trends = Trends::new(&[snapshot.clone(), snapshot], 12);
+        for dwell in trends.dwell {
+            assert_eq!(dwell.tasks, 0);
+            assert_eq!(format!("{:.1}", dwell.mean_days), "0.0");
+        }
+    }
+}
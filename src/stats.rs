@@ -0,0 +1,175 @@
+use crate::model::{Task, TaskStatus};
+use crate::query::DateField;
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// How many of the items listed are shown as oldest and largest
+const TOP: usize = 10;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub count: usize,
+}
+
+/// An open task with the day it became actionable
+#[derive(Debug, Clone, Serialize)]
+pub struct Aged {
+    /// YYYY-MM-DD
+    pub since: String,
+    pub task: Task,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub total: usize,
+    pub by_status: Vec<Count>,
+    pub by_context: Vec<Count>,
+    pub by_project: Vec<Count>,
+    pub no_context: usize,
+    pub no_status: usize,
+    /// Open tasks per due date range
+    pub due: Vec<Count>,
+    /// Open tasks with the earliest start or visible date
+    pub oldest: Vec<Aged>,
+    /// Projects with the most open tasks
+    pub largest_projects: Vec<Count>,
+}
+
+fn counts<'a>(names: impl Iterator<Item = &'a str>) -> Vec<Count> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for name in names {
+        *counts.entry(name).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .map(|(name, count)| Count {
+            name: name.to_string(),
+            count,
+        })
+        .collect()
+}
+
+fn due_range(task: &Task, today: NaiveDate) -> &'static str {
+    match DateField::Due.of(task).map(|d| (d - today).num_days()) {
+        None => "no due date",
+        Some(days) if days < 0 => "overdue",
+        Some(0) => "today",
+        Some(days) if days <= 7 => "next 7 days",
+        Some(days) if days <= 30 => "next 30 days",
+        Some(_) => "later",
+    }
+}
+
+impl Stats {
+    pub fn new(tasks: &[Task], today: NaiveDate) -> Stats {
+        let open: Vec<&Task> = tasks
+            .iter()
+            .filter(|t| t.status != TaskStatus::Done)
+            .collect();
+
+        let statuses: Vec<String> = tasks.iter().map(|t| t.status.to_string()).collect();
+        let by_status = TaskStatus::all()
+            .into_iter()
+            .map(|s| s.to_string())
+            .filter_map(|s| {
+                let count = statuses.iter().filter(|t| **t == s).count();
+                (count > 0).then_some(Count { name: s, count })
+            })
+            .collect();
+
+        let due_counts = counts(open.iter().map(|t| due_range(t, today)));
+        let due = [
+            "overdue",
+            "today",
+            "next 7 days",
+            "next 30 days",
+            "later",
+            "no due date",
+        ]
+        .iter()
+        .filter_map(|range| due_counts.iter().find(|c| c.name == *range).cloned())
+        .collect();
+
+        let mut oldest: Vec<Aged> = open
+            .iter()
+            .filter_map(|t| {
+                let since = [DateField::Start, DateField::Visible]
+                    .iter()
+                    .filter_map(|f| f.of(t))
+                    .filter(|d| *d <= today)
+                    .min()?;
+                Some(Aged {
+                    since: since.to_string(),
+                    task: (*t).clone(),
+                })
+            })
+            .collect();
+        oldest.sort_by(|a, b| a.since.cmp(&b.since));
+        oldest.truncate(TOP);
+
+        let mut largest_projects = counts(open.iter().map(|t| t.project.as_str()));
+        largest_projects.sort_by_key(|c| std::cmp::Reverse(c.count));
+        largest_projects.truncate(TOP);
+
+        Stats {
+            total: tasks.len(),
+            by_status,
+            by_context: counts(tasks.iter().flat_map(|t| &t.contexts).map(String::as_str)),
+            by_project: counts(tasks.iter().map(|t| t.project.as_str())),
+            no_context: tasks.iter().filter(|t| t.contexts.is_empty()).count(),
+            no_status: tasks
+                .iter()
+                .filter(|t| t.status == TaskStatus::NoStatus)
+                .count(),
+            due,
+            oldest,
+            largest_projects,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(counts: &[Count], name: &str) -> usize {
+        counts
+            .iter()
+            .find(|c| c.name == name)
+            .map_or(0, |c| c.count)
+    }
+
+    #[test]
+    fn counts_done_tasks_and_ages_by_task_dates() {
+        let tasks: Vec<Task> = [
+            "- paint door @wip @s20240901 #xhome",
+            "- fix roof @todo @v20240801 @s20240915",
+            "- buy paint @done @c20240930",
+            "- plan trip @todo @s20250101",
+            "- call plumber @todo",
+        ]
+        .iter()
+        .map(|l| Task::from(l, "house.md"))
+        .collect();
+        let today = NaiveDate::from_ymd_opt(2024, 10, 1).unwrap();
+        let stats = Stats::new(&tasks, today);
+
+        assert_eq!(stats.total, 5);
+        assert_eq!(count(&stats.by_status, "@done"), 1);
+        assert_eq!(count(&stats.by_status, "@todo"), 3);
+        let oldest: Vec<(&str, String)> = stats
+            .oldest
+            .iter()
+            .map(|a| (a.since.as_str(), a.task.title()))
+            .collect();
+        assert_eq!(
+            oldest,
+            vec![
+                ("2024-08-01", "fix roof".to_string()),
+                ("2024-09-01", "paint door".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use crate::edit::EditError;
+use crate::model::{Task, TaskDates, TaskStatus};
+use crate::query::parse_status;
+use crate::refile::{file_imported, split_projects, Imported};
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Taskwarrior keeps its uuid in the text of imported tasks as `uuid:<uuid>`
+static UUID_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s*\buuid:([0-9a-fA-F-]{36})\b").unwrap());
+
+const DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// A task as in the JSON of `task export` and `task import`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TwTask {
+    pub uuid: String,
+    pub description: String,
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheduled: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    /// Set while the task is being worked on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+}
+
+/// A marker date as the start of that day in UTC
+fn to_tw_date(date: &Option<String>) -> Option<String> {
+    let date = date.as_deref().and_then(TaskDates::to_date)?;
+    let midnight = Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()?;
+    Some(midnight.with_timezone(&Utc).format(DATE_FORMAT).to_string())
+}
+
+/// A Taskwarrior date as the local day of a marker, e.g. `@d20240131`
+fn from_tw_date(marker: char, date: &Option<String>) -> Option<String> {
+    let utc = NaiveDateTime::parse_from_str(date.as_deref()?, DATE_FORMAT).ok()?;
+    let day: NaiveDate = Utc
+        .from_utc_datetime(&utc)
+        .with_timezone(&Local)
+        .date_naive();
+    Some(format!("@{}{}", marker, day.format("%Y%m%d")))
+}
+
+fn status_tag(status: TaskStatus) -> Option<String> {
+    match status {
+        TaskStatus::NoStatus | TaskStatus::Done => None,
+        status => Some(status.to_string().trim_start_matches('@').to_string()),
+    }
+}
+
+/// A task for Taskwarrior. The project is the file, unless the task has a
+/// `+project` of its own. The gtd status is kept as a tag, the start date
+/// becomes `scheduled` and the visible date `wait`.
+pub fn to_taskwarrior(task: &Task) -> TwTask {
+    let dates = task.dates.clone().unwrap_or_default();
+    let uuid = UUID_RE
+        .captures(&task.description)
+        .map(|c| c[1].to_lowercase())
+        .unwrap_or_else(|| task.stable_id().to_string());
+    let (description, projects) = split_projects(&UUID_RE.replace_all(&task.title(), ""));
+    let project = projects.into_iter().next().or_else(|| {
+        Path::new(&task.project)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+    });
+    TwTask {
+        uuid,
+        description,
+        status: match task.status {
+            TaskStatus::Done => "completed".into(),
+            _ => "pending".into(),
+        },
+        project,
+        tags: task
+            .contexts
+            .iter()
+            .map(|c| c.trim_start_matches("#x").to_string())
+            .chain(status_tag(task.status))
+            .collect(),
+        due: to_tw_date(&dates.due),
+        wait: to_tw_date(&dates.visible),
+        scheduled: to_tw_date(&dates.start),
+        end: to_tw_date(&dates.completed),
+        start: None,
+        priority: task.priority.map(|p| {
+            match p {
+                'A' => "H",
+                'B' => "M",
+                _ => "L",
+            }
+            .to_string()
+        }),
+    }
+}
+
+pub fn export(tasks: &[Task]) -> String {
+    let tasks: Vec<TwTask> = tasks.iter().map(to_taskwarrior).collect();
+    serde_json::to_string_pretty(&tasks).unwrap() + "\n"
+}
+
+/// A Taskwarrior task as a Markdown list item with gtd markers and its
+/// project, `None` for deleted and recurring tasks. Tags named after a status
+/// set the status and other tags become contexts.
+pub fn from_taskwarrior(task: &TwTask) -> Option<Imported> {
+    let mut status = match task.status.as_str() {
+        "completed" => TaskStatus::Done,
+        "pending" | "waiting" => TaskStatus::Todo,
+        _ => return None,
+    };
+    if status != TaskStatus::Done && task.start.is_some() {
+        status = TaskStatus::Wip;
+    }
+    let mut contexts: Vec<String> = vec![];
+    for tag in &task.tags {
+        match parse_status(tag) {
+            Ok(TaskStatus::NoStatus) | Err(_) => contexts.push(format!(
+                "#x{}",
+                tag.replace(|c: char| !c.is_alphanumeric(), "_")
+            )),
+            Ok(s) if status != TaskStatus::Done => status = s,
+            Ok(_) => (),
+        }
+    }
+
+    let mut words: Vec<String> = vec![task.description.clone()];
+    words.push(format!("uuid:{}", task.uuid));
+    words.push(status.to_string());
+    words.extend(contexts);
+    words.extend(task.priority.as_deref().and_then(|p| match p {
+        "H" => Some("@pA".to_string()),
+        "M" => Some("@pB".to_string()),
+        "L" => Some("@pC".to_string()),
+        _ => None,
+    }));
+    words.extend(from_tw_date('s', &task.scheduled));
+    words.extend(from_tw_date('d', &task.due));
+    words.extend(from_tw_date('v', &task.wait));
+    words.extend(from_tw_date('c', &task.end));
+    Some(Imported {
+        project: task.project.clone(),
+        item: format!("- {}", words.join(" ")),
+    })
+}
+
+/// Read `task export` output, a JSON array or one task per line
+pub fn parse(content: &str) -> Result<Vec<TwTask>, serde_json::Error> {
+    match serde_json::from_str(content) {
+        Ok(tasks) => Ok(tasks),
+        Err(e) if content.trim_start().starts_with('[') => Err(e),
+        Err(_) => content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect(),
+    }
+}
+
+/// Add Taskwarrior tasks to their project files or the inbox, see
+/// [file_imported]
+pub fn import(
+    tasks: &[TwTask],
+    inbox_path: &Path,
+    project_files: &[PathBuf],
+) -> Result<Vec<(PathBuf, usize)>, EditError> {
+    let items: Vec<Imported> = tasks.iter().filter_map(from_taskwarrior).collect();
+    let command = format!("import {} Taskwarrior tasks", items.len());
+    file_imported(&items, inbox_path, project_files, &command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(line: &str) -> (Task, Task) {
+        let task = Task::from(line, "house.md");
+        let imported = from_taskwarrior(&to_taskwarrior(&task)).unwrap();
+        assert_eq!(imported.project.as_deref(), Some("house"), "{line}");
+        (task, Task::from(&imported.item, "house.md"))
+    }
+
+    #[test]
+    fn round_trips_status_contexts_dates_and_priority() {
+        for line in [
+            "- paint door @wip #xhome @pA @s20241001 @d20241031",
+            "- fix roof @review #xhome #xzz @v20241101",
+            "- buy paint @done #xshop @c20240930 @pC",
+            "- call plumber",
+        ] {
+            let (task, back) = round_trip(line);
+            assert_eq!(
+                back.title(),
+                format!("{} uuid:{}", task.title(), task.stable_id())
+            );
+            assert_eq!(back.contexts, task.contexts, "{line}");
+            assert_eq!(back.dates, task.dates, "{line}");
+            assert_eq!(back.priority, task.priority.map(|p| p.min('C')), "{line}");
+            // Taskwarrior has no tasks without a status
+            match task.status {
+                TaskStatus::NoStatus => assert_eq!(back.status, TaskStatus::Todo),
+                status => assert_eq!(back.status, status, "{line}"),
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_the_uuid_of_imported_tasks() {
+        let tw = TwTask {
+            uuid: "0b9e0f3a-6f2e-4c55-9a0a-3d1c7a7f9e21".into(),
+            description: "renew passport".into(),
+            status: "pending".into(),
+            tags: vec!["admin".into(), "wip".into()],
+            due: to_tw_date(&Some("20241031".into())),
+            ..Default::default()
+        };
+        let imported = from_taskwarrior(&tw).unwrap();
+        assert_eq!(imported.project, None);
+        let task = Task::from(&imported.item, "inbox.md");
+        let back = to_taskwarrior(&task);
+        assert_eq!(back.uuid, tw.uuid);
+        assert_eq!(back.description, "renew passport");
+        assert_eq!(back.status, "pending");
+        assert_eq!(back.tags, vec!["admin".to_string(), "wip".to_string()]);
+        assert_eq!(back.due, tw.due);
+    }
+
+    #[test]
+    fn a_project_left_in_the_inbox_stays_the_project() {
+        let tw = TwTask {
+            uuid: "0b9e0f3a-6f2e-4c55-9a0a-3d1c7a7f9e21".into(),
+            description: "fix gutter".into(),
+            status: "pending".into(),
+            project: Some("house".into()),
+            ..Default::default()
+        };
+        let imported = from_taskwarrior(&tw).unwrap();
+        assert_eq!(imported.project.as_deref(), Some("house"));
+        assert!(!imported.item.contains("+house"));
+
+        // As filed in the inbox when there is no house.md
+        let item = format!("{} +house", imported.item);
+        let back = to_taskwarrior(&Task::from(&item, "inbox.md"));
+        assert_eq!(back.project.as_deref(), Some("house"));
+        assert_eq!(back.description, "fix gutter");
+        assert_eq!(back.uuid, tw.uuid);
+    }
+}
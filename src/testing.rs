@@ -0,0 +1,19 @@
+//! Helpers for the tests that touch the file system
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Once;
+
+/// An empty directory of its own for the test called `name`
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gtd-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Keep the journal and other state of the tests out of the user's data
+/// directory
+pub fn use_test_data_dir() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| std::env::set_var("XDG_DATA_HOME", temp_dir("data")));
+}
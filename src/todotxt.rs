@@ -0,0 +1,213 @@
+use crate::edit::EditError;
+use crate::model::{Task, TaskDates, TaskStatus};
+use crate::query::parse_status;
+use crate::refile::{file_imported, split_projects, Imported};
+use chrono::NaiveDate;
+use std::path::{Path, PathBuf};
+
+fn iso_date(date: &Option<String>) -> Option<String> {
+    date.as_deref()
+        .and_then(TaskDates::to_date)
+        .map(|d| d.format("%Y-%m-%d").to_string())
+}
+
+/// The project file name as a todo.txt `+project`
+fn project_tag(project: &str) -> String {
+    let stem = Path::new(project)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or(project.to_string());
+    format!("+{}", stem.split_whitespace().collect::<Vec<_>>().join("-"))
+}
+
+/// A task as a todo.txt line. The project is the file, unless the task has
+/// a `+project` of its own. The start date becomes `t:` (threshold) and a
+/// status other than done becomes `status:`.
+pub fn to_todotxt(task: &Task) -> String {
+    let dates = task.dates.clone().unwrap_or_default();
+    let mut words: Vec<String> = vec![];
+    if task.status == TaskStatus::Done {
+        words.push("x".into());
+        words.extend(iso_date(&dates.completed));
+    } else if let Some(priority) = task.priority {
+        words.push(format!("({})", priority));
+    }
+    let (title, projects) = split_projects(&task.title());
+    words.push(title);
+    match projects.is_empty() {
+        true => words.push(project_tag(&task.project)),
+        false => words.extend(projects.iter().map(|p| format!("+{}", p))),
+    }
+    words.extend(
+        task.contexts
+            .iter()
+            .map(|c| format!("@{}", c.trim_start_matches("#x"))),
+    );
+    if let (TaskStatus::Done, Some(priority)) = (task.status, task.priority) {
+        words.push(format!("pri:{}", priority));
+    }
+    if !matches!(task.status, TaskStatus::NoStatus | TaskStatus::Done) {
+        words.push(format!(
+            "status:{}",
+            task.status.to_string().trim_start_matches('@')
+        ));
+    }
+    words.extend(iso_date(&dates.due).map(|d| format!("due:{}", d)));
+    words.extend(iso_date(&dates.start).map(|d| format!("t:{}", d)));
+    words.join(" ")
+}
+
+pub fn export(tasks: &[Task]) -> String {
+    tasks.iter().map(|t| to_todotxt(t) + "\n").collect()
+}
+
+fn parse_date(word: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()
+}
+
+fn marker_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+fn is_person(word: &str) -> bool {
+    word[1..].starts_with(|c: char| c.is_ascii_uppercase())
+}
+
+/// A todo.txt line as a Markdown list item with gtd markers and its first
+/// `+project`, `None` for an empty line. Contexts starting with a capital are
+/// kept as people.
+pub fn from_todotxt(line: &str) -> Option<Imported> {
+    let mut words = line.split_whitespace().peekable();
+    words.peek()?;
+
+    let mut markers: Vec<String> = vec![];
+    if words.peek() == Some(&"x") {
+        words.next();
+        markers.push("@done".into());
+        if let Some(completed) = words.peek().and_then(|w| parse_date(w)) {
+            words.next();
+            markers.push(format!("@c{}", marker_date(completed)));
+        }
+    }
+    let priority = |w: &str| {
+        let c: Vec<char> = w.chars().collect();
+        match c.as_slice() {
+            ['(', p, ')'] if p.is_ascii_uppercase() => Some(*p),
+            _ => None,
+        }
+    };
+    if let Some(p) = words.peek().and_then(|w| priority(w)) {
+        words.next();
+        markers.push(format!("@p{}", p));
+    }
+    // The creation date has no marker
+    if words.peek().and_then(|w| parse_date(w)).is_some() {
+        words.next();
+    }
+
+    let mut project = None;
+    let mut text: Vec<&str> = vec![];
+    for word in words {
+        let (key, value) = word.split_once(':').unwrap_or((word, ""));
+        match (key, value) {
+            ("due", date) if parse_date(date).is_some() => {
+                markers.push(format!("@d{}", marker_date(parse_date(date)?)))
+            }
+            ("t", date) if parse_date(date).is_some() => {
+                markers.push(format!("@s{}", marker_date(parse_date(date)?)))
+            }
+            ("pri", p) if p.len() == 1 && p.chars().all(|c| c.is_ascii_uppercase()) => {
+                markers.push(format!("@p{}", p))
+            }
+            ("status", status) if parse_status(status).is_ok() => {
+                match parse_status(status).ok()? {
+                    TaskStatus::NoStatus => (),
+                    status => markers.push(status.to_string()),
+                }
+            }
+            _ if word.len() > 1 && word.starts_with('+') && project.is_none() => {
+                project = Some(word[1..].to_string())
+            }
+            // `@Name` with a capital is a person, see [Task::people]
+            _ if word.len() > 1 && word.starts_with('@') && !is_person(word) => {
+                markers.push(format!("#x{}", &word[1..]))
+            }
+            _ => text.push(word),
+        }
+    }
+    // Without any marker the item would not be a task in a project file
+    if markers.is_empty() {
+        markers.push(TaskStatus::Todo.to_string());
+    }
+    let item = format!("- {} {}", text.join(" "), markers.join(" "));
+    Some(Imported {
+        project,
+        item: item.trim_end().to_string(),
+    })
+}
+
+/// Add the tasks of a todo.txt file to their project files or the inbox, see
+/// [file_imported]
+pub fn import(
+    content: &str,
+    inbox_path: &Path,
+    project_files: &[PathBuf],
+) -> Result<Vec<(PathBuf, usize)>, EditError> {
+    let items: Vec<Imported> = content.lines().filter_map(from_todotxt).collect();
+    let command = format!("import {} todo.txt tasks", items.len());
+    file_imported(&items, inbox_path, project_files, &command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_status_contexts_dates_and_priority() {
+        for line in [
+            "- paint door @wip #xhome @pA @s20241001 @d20241031",
+            "- fix roof @review #xhome #xzz",
+            "- buy paint @done #xshop @c20240930 @pC",
+            "- call plumber",
+        ] {
+            let task = Task::from(line, "house.md");
+            let imported = from_todotxt(&to_todotxt(&task)).unwrap();
+            assert_eq!(imported.project.as_deref(), Some("house"), "{line}");
+            let back = Task::from(&imported.item, "house.md");
+            assert_eq!(back.title(), task.title(), "{line}");
+            match task.status {
+                TaskStatus::NoStatus => assert_eq!(back.status, TaskStatus::Todo),
+                status => assert_eq!(back.status, status, "{line}"),
+            }
+            assert_eq!(back.contexts, task.contexts, "{line}");
+            assert_eq!(back.dates, task.dates, "{line}");
+            assert_eq!(back.priority, task.priority, "{line}");
+        }
+    }
+
+    #[test]
+    fn round_trips_todotxt_lines() {
+        for (line, file) in [
+            ("(A) call mom +family @phone due:2024-10-31", "family.md"),
+            ("x 2024-09-30 buy paint +house @shop pri:C", "house.md"),
+            // Filed in the inbox when there is no project file
+            ("draft plan +work status:wip t:2024-10-01", "inbox.md"),
+        ] {
+            let imported = from_todotxt(line).unwrap();
+            let item = match file {
+                "inbox.md" => format!("{} +{}", imported.item, imported.project.unwrap()),
+                _ => imported.item,
+            };
+            assert_eq!(to_todotxt(&Task::from(&item, file)), line);
+        }
+    }
+
+    #[test]
+    fn takes_the_project_out_of_the_description() {
+        let imported = from_todotxt("call mom +family +urgent @phone").unwrap();
+        assert_eq!(imported.project.as_deref(), Some("family"));
+        assert_eq!(imported.item, "- call mom +urgent #xphone");
+        let imported = from_todotxt("fix gutter +house").unwrap();
+        assert_eq!(imported.item, "- fix gutter @todo");
+    }
+}
@@ -0,0 +1,678 @@
+use crate::crawl::{read_tasks, NotesCache};
+use crate::fuzzy;
+use crate::journal;
+use crate::limits::WipLimits;
+use crate::model::{ServerConfig, Task, TaskStatus};
+use crate::query::{DateValue, Query};
+use crate::update::{defer, set_status};
+use chrono::{Local, NaiveDate};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{enable_raw_mode, EnterAlternateScreen};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Tabs, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashSet;
+use std::env::var;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+const HELP: &str =
+    "q quit  / search  tab pivot  t w r x W M n status  s star  f defer  e edit  u undo";
+
+/// A pivot of the task list
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Tab {
+    All,
+    Status(TaskStatus),
+    Context(String),
+}
+
+impl Tab {
+    fn contains(&self, task: &Task) -> bool {
+        match self {
+            Tab::All => true,
+            Tab::Status(status) => task.status == *status,
+            Tab::Context(context) => task.contexts.contains(context),
+        }
+    }
+
+    fn title(&self) -> String {
+        match self {
+            Tab::All => "All".into(),
+            Tab::Status(status) => status.to_string(),
+            Tab::Context(context) => context.clone(),
+        }
+    }
+}
+
+enum Mode {
+    Normal,
+    Search,
+    Defer(String),
+}
+
+struct App {
+    file_paths: Vec<PathBuf>,
+    filter: Query,
+    limits: WipLimits,
+    server: Option<ServerConfig>,
+    today: NaiveDate,
+    /// Every task in the knowledge base, to check the WIP limits against
+    all_tasks: Vec<Task>,
+    /// The tasks that match the filter
+    tasks: Vec<Task>,
+    /// Notes of the tasks shown so far, until the next reload
+    notes: NotesCache,
+    starred: HashSet<String>,
+    tabs: Vec<Tab>,
+    tab: usize,
+    search: String,
+    mode: Mode,
+    list: ListState,
+    message: String,
+    /// A status change that exceeds a limit, made anyway if asked again
+    force: Option<TaskStatus>,
+}
+
+fn status_color(status: TaskStatus) -> Color {
+    match status {
+        TaskStatus::Todo => Color::Green,
+        TaskStatus::Wip | TaskStatus::Week | TaskStatus::Month => Color::Red,
+        TaskStatus::Review => Color::Yellow,
+        TaskStatus::NoStatus | TaskStatus::Done => Color::DarkGray,
+    }
+}
+
+fn server_client() -> Result<reqwest::blocking::Client, reqwest::Error> {
+    reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(2))
+        .build()
+}
+
+/// Descriptions of the tasks starred on the server
+fn fetch_starred(server: &ServerConfig) -> Result<HashSet<String>, reqwest::Error> {
+    let tasks: Vec<Task> = server_client()?
+        .get(server.host.clone() + "/tasks?include_future=true")
+        .send()?
+        .error_for_status()?
+        .json()?;
+    Ok(tasks
+        .into_iter()
+        .filter(|t| t.starred)
+        .map(|t| t.description)
+        .collect())
+}
+
+fn toggle_star(server: &ServerConfig, description: &str) -> Result<(), reqwest::Error> {
+    server_client()?
+        .post(server.host.clone() + "/star")
+        .body(description.to_string())
+        .header("Authorization", "Basic ".to_owned() + &server.basic_token())
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// What the event loop does after a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Continue,
+    /// Open the selected task in $EDITOR
+    Edit,
+    Quit,
+}
+
+impl App {
+    fn new(
+        file_paths: Vec<PathBuf>,
+        filter: Query,
+        limits: WipLimits,
+        server: Option<ServerConfig>,
+        today: NaiveDate,
+    ) -> App {
+        let mut app = App {
+            file_paths,
+            filter,
+            limits,
+            server,
+            today,
+            all_tasks: vec![],
+            tasks: vec![],
+            notes: NotesCache::default(),
+            starred: HashSet::new(),
+            tabs: vec![Tab::All],
+            tab: 0,
+            search: String::new(),
+            mode: Mode::Normal,
+            list: ListState::default(),
+            message: String::new(),
+            force: None,
+        };
+        app.reload();
+        app
+    }
+
+    fn reload(&mut self) {
+        self.notes = NotesCache::default();
+        self.all_tasks = self.file_paths.iter().flat_map(|p| read_tasks(p)).collect();
+        self.tasks = self
+            .all_tasks
+            .iter()
+            .filter(|t| self.filter.matches(t, self.today))
+            .cloned()
+            .collect();
+
+        let current = self.tabs.get(self.tab).cloned();
+        let statuses = TaskStatus::all()
+            .into_iter()
+            .filter(|s| self.tasks.iter().any(|t| t.status == *s))
+            .map(Tab::Status);
+        let mut contexts: Vec<String> =
+            self.tasks.iter().flat_map(|t| t.contexts.clone()).collect();
+        contexts.sort();
+        contexts.dedup();
+        self.tabs = [Tab::All]
+            .into_iter()
+            .chain(statuses)
+            .chain(contexts.into_iter().map(Tab::Context))
+            .collect();
+        self.tab = current
+            .and_then(|c| self.tabs.iter().position(|t| *t == c))
+            .unwrap_or(0);
+        self.clamp_selection();
+    }
+
+    /// The tasks in the current tab that match the search, best match first
+    fn visible(&self) -> Vec<&Task> {
+        let in_tab: Vec<&Task> = self
+            .tasks
+            .iter()
+            .filter(|t| self.tabs[self.tab].contains(t))
+            .collect();
+        if self.search.trim().is_empty() {
+            return in_tab;
+        }
+        fuzzy::rank(&self.search, &in_tab, |t| {
+            format!("{} {}", t.title(), t.project)
+        })
+        .into_iter()
+        .map(|(_, t)| *t)
+        .collect()
+    }
+
+    fn selected(&self) -> Option<Task> {
+        let i = self.list.selected()?;
+        self.visible().get(i).map(|t| (*t).clone())
+    }
+
+    fn clamp_selection(&mut self) {
+        let len = self.visible().len();
+        let selected = match len {
+            0 => None,
+            _ => Some(self.list.selected().unwrap_or(0).min(len - 1)),
+        };
+        self.list.select(selected);
+    }
+
+    fn move_selection(&mut self, by: isize) {
+        let len = self.visible().len() as isize;
+        if len > 0 {
+            let i = self.list.selected().unwrap_or(0) as isize;
+            self.list.select(Some((i + by).rem_euclid(len) as usize));
+        }
+    }
+
+    fn switch_tab(&mut self, by: isize) {
+        let len = self.tabs.len() as isize;
+        self.tab = (self.tab as isize + by).rem_euclid(len) as usize;
+        self.list.select(Some(0));
+        self.clamp_selection();
+    }
+
+    fn change_status(&mut self, status: TaskStatus) {
+        let Some(task) = self.selected() else {
+            return;
+        };
+        let forced = self.force.take() == Some(status);
+        let exceeded = self.limits.exceeded_by(&self.all_tasks, &task, status);
+        if !exceeded.is_empty() && !forced {
+            self.message = format!("{}, press again to force", exceeded[0]);
+            self.force = Some(status);
+            return;
+        }
+        self.message = match set_status(&task, status, self.today) {
+            Ok(()) => format!("Set '{}' to {}", task.title(), status),
+            Err(e) => format!("Could not set status: {}", e),
+        };
+        self.reload();
+    }
+
+    fn star(&mut self) {
+        let Some(task) = self.selected() else {
+            return;
+        };
+        let Some(server) = &self.server else {
+            self.message = "Starring needs a server in the config".into();
+            return;
+        };
+        self.message = match toggle_star(server, &task.description) {
+            Ok(()) => {
+                if !self.starred.remove(&task.description) {
+                    self.starred.insert(task.description.clone());
+                }
+                format!("Toggled the star of '{}'", task.title())
+            }
+            Err(e) => format!("Could not star: {}", e),
+        };
+        self.reload();
+    }
+
+    fn defer(&mut self, input: &str) {
+        let Some(task) = self.selected() else {
+            return;
+        };
+        let date = input.parse::<DateValue>().and_then(|d| {
+            d.resolve(self.today)
+                .ok_or_else(|| format!("Date out of range: {}", input))
+        });
+        self.message = match date {
+            Ok(date) => match defer(&task, date) {
+                Ok(()) => format!("Deferred '{}' until {}", task.title(), date),
+                Err(e) => format!("Could not defer: {}", e),
+            },
+            Err(e) => e,
+        };
+        self.reload();
+    }
+
+    fn undo(&mut self) {
+        self.message = match journal::undo(1) {
+            Ok(undone) => match undone.first() {
+                Some(entry) => format!("Undid {}", entry.command),
+                None => "Nothing to undo".into(),
+            },
+            Err(e) => format!("Could not undo: {}", e),
+        };
+        self.reload();
+    }
+
+    /// Open the task in $EDITOR at its line, leaving the TUI for as long as
+    /// the editor runs
+    fn edit(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let Some(source) = self.selected().and_then(|t| t.source) else {
+            return Ok(());
+        };
+        let editor = var("EDITOR").unwrap_or("vi".into());
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+
+        ratatui::restore();
+        let status = Command::new(program)
+            .args(words)
+            .arg(format!("+{}", source.line))
+            .arg(&source.path)
+            .status();
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        terminal.clear()?;
+
+        if let Err(e) = status {
+            self.message = format!("Could not run {}: {}", program, e);
+        }
+        self.reload();
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: KeyCode) -> Action {
+        let force = self.force.take();
+        match &mut self.mode {
+            Mode::Search => match key {
+                KeyCode::Esc => {
+                    self.search.clear();
+                    self.mode = Mode::Normal;
+                }
+                KeyCode::Enter => self.mode = Mode::Normal,
+                KeyCode::Backspace => {
+                    self.search.pop();
+                }
+                KeyCode::Char(c) => self.search.push(c),
+                KeyCode::Down => self.move_selection(1),
+                KeyCode::Up => self.move_selection(-1),
+                _ => (),
+            },
+            Mode::Defer(input) => match key {
+                KeyCode::Esc => self.mode = Mode::Normal,
+                KeyCode::Enter => {
+                    let input = input.clone();
+                    self.mode = Mode::Normal;
+                    self.defer(&input);
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => (),
+            },
+            Mode::Normal => {
+                self.message.clear();
+                let status = match key {
+                    KeyCode::Char('t') => Some(TaskStatus::Todo),
+                    KeyCode::Char('w') => Some(TaskStatus::Wip),
+                    KeyCode::Char('r') => Some(TaskStatus::Review),
+                    KeyCode::Char('x') => Some(TaskStatus::Done),
+                    KeyCode::Char('W') => Some(TaskStatus::Week),
+                    KeyCode::Char('M') => Some(TaskStatus::Month),
+                    KeyCode::Char('n') => Some(TaskStatus::NoStatus),
+                    _ => None,
+                };
+                if let Some(status) = status {
+                    self.force = force;
+                    self.change_status(status);
+                    return Action::Continue;
+                }
+                match key {
+                    KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+                    KeyCode::Char('j') | KeyCode::Down => self.move_selection(1),
+                    KeyCode::Char('k') | KeyCode::Up => self.move_selection(-1),
+                    KeyCode::Tab | KeyCode::Right => self.switch_tab(1),
+                    KeyCode::BackTab | KeyCode::Left => self.switch_tab(-1),
+                    KeyCode::Char('/') => self.mode = Mode::Search,
+                    KeyCode::Char('s') => self.star(),
+                    KeyCode::Char('f') => self.mode = Mode::Defer(String::new()),
+                    KeyCode::Char('e') => return Action::Edit,
+                    KeyCode::Char('u') => self.undo(),
+                    _ => (),
+                }
+            }
+        }
+        self.clamp_selection();
+        Action::Continue
+    }
+
+    fn task_item(task: &Task, starred: bool) -> ListItem<'static> {
+        let mut spans = vec![
+            Span::raw(if starred { "* " } else { "  " }),
+            Span::styled(
+                format!("{:<8}", task.status.to_string()),
+                Style::default().fg(status_color(task.status)),
+            ),
+            Span::raw(task.title()),
+            Span::raw(" "),
+            Span::styled(task.project.clone(), Style::default().dim()),
+        ];
+        for context in &task.contexts {
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(
+                context.clone(),
+                Style::default().fg(Color::Blue),
+            ));
+        }
+        ListItem::new(Line::from(spans))
+    }
+
+    fn details(task: &Task, notes: &mut NotesCache) -> Vec<Line<'static>> {
+        let field = |name: &str, value: String| {
+            Line::from(vec![
+                Span::styled(format!("{:<10}", name), Style::default().dim()),
+                Span::raw(value),
+            ])
+        };
+        let mut lines = vec![
+            Line::from(task.title()).bold(),
+            Line::default(),
+            field("Project", task.project.clone()),
+            field("Status", task.status.to_string()),
+            field("Contexts", task.contexts.join(" ")),
+        ];
+        if let Some(priority) = task.priority {
+            lines.push(field("Priority", priority.to_string()));
+        }
+        if let Some(dates) = &task.dates {
+            lines.push(field("Dates", dates.markers().join(" ")));
+        }
+        if let Some(source) = &task.source {
+            lines.push(field("Source", source.to_string()));
+        }
+        let notes = notes.notes(task);
+        if !notes.is_empty() {
+            lines.push(Line::default());
+            lines.extend(notes.into_iter().map(Line::from));
+        }
+        lines
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [tabs_area, main_area, status_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(main_area);
+
+        let titles: Vec<String> = self
+            .tabs
+            .iter()
+            .map(|tab| {
+                let count = self.tasks.iter().filter(|t| tab.contains(t)).count();
+                format!("{} ({})", tab.title(), count)
+            })
+            .collect();
+        frame.render_widget(
+            Tabs::new(titles)
+                .select(self.tab)
+                .highlight_style(Style::default().reversed()),
+            tabs_area,
+        );
+
+        let visible = self.visible();
+        let items: Vec<ListItem> = visible
+            .iter()
+            .map(|t| App::task_item(t, self.starred.contains(&t.description)))
+            .collect();
+        let selected = self
+            .list
+            .selected()
+            .and_then(|i| visible.get(i))
+            .map(|t| (*t).clone());
+        let details = selected
+            .map(|t| App::details(&t, &mut self.notes))
+            .unwrap_or_default();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::RIGHT))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+        frame.render_widget(
+            Paragraph::new(details).wrap(Wrap { trim: false }),
+            detail_area,
+        );
+
+        let status_line = match &self.mode {
+            Mode::Search => format!("/{}", self.search),
+            Mode::Defer(input) => {
+                format!("Defer until (e.g. tomorrow, +3d, 2024-01-31): {}", input)
+            }
+            Mode::Normal if !self.message.is_empty() => self.message.clone(),
+            Mode::Normal if !self.search.is_empty() => format!("/{}  {}", self.search, HELP),
+            Mode::Normal => HELP.to_string(),
+        };
+        frame.render_widget(Paragraph::new(status_line).dim(), status_area);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match self.handle_key(key.code) {
+                Action::Continue => (),
+                Action::Edit => self.edit(terminal)?,
+                Action::Quit => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Run the interactive task list on the tasks in `file_paths` that match
+/// `filter`. Edits are checked against `limits` and recorded in the journal.
+pub fn run(
+    file_paths: Vec<PathBuf>,
+    filter: Query,
+    limits: WipLimits,
+    server: Option<ServerConfig>,
+) -> io::Result<()> {
+    let mut app = App::new(
+        file_paths,
+        filter,
+        limits,
+        server,
+        Local::now().date_naive(),
+    );
+    if let Some(server) = &app.server {
+        match fetch_starred(server) {
+            Ok(starred) => app.starred = starred,
+            Err(e) => app.message = format!("Could not fetch the stars: {}", e),
+        }
+    }
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{temp_dir, use_test_data_dir};
+    use std::fs;
+
+    fn app(name: &str, content: &str, limits: &str) -> (App, PathBuf) {
+        use_test_data_dir();
+        let dir = temp_dir(name);
+        let path = dir.join("house.md");
+        fs::write(&path, content).unwrap();
+        let limits = serde_json::from_str(limits).unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        (
+            App::new(vec![path.clone()], Query::All, limits, None, today),
+            path,
+        )
+    }
+
+    fn press(app: &mut App, keys: &str) -> Action {
+        keys.chars()
+            .map(|c| app.handle_key(KeyCode::Char(c)))
+            .last()
+            .unwrap_or(Action::Continue)
+    }
+
+    fn titles(app: &App) -> Vec<String> {
+        app.visible().iter().map(|t| t.title()).collect()
+    }
+
+    #[test]
+    fn moves_between_tasks_and_tabs() {
+        let (mut app, path) = app(
+            "tui-tabs",
+            "- paint door @wip #xhome\n- fix roof @todo\n- mow lawn @todo #xhome\n",
+            "{}",
+        );
+        let tabs: Vec<String> = app.tabs.iter().map(Tab::title).collect();
+        assert_eq!(tabs, ["All", "@wip", "@todo", "#xhome"]);
+        assert_eq!(app.list.selected(), Some(0));
+        press(&mut app, "k");
+        assert_eq!(app.list.selected(), Some(2));
+        press(&mut app, "jj");
+        assert_eq!(app.list.selected(), Some(1));
+
+        app.handle_key(KeyCode::BackTab);
+        assert_eq!(titles(&app), ["paint door", "mow lawn"]);
+        assert_eq!(app.list.selected(), Some(0));
+        app.handle_key(KeyCode::Tab);
+        assert_eq!(app.tab, 0);
+        assert_eq!(press(&mut app, "q"), Action::Quit);
+        assert_eq!(press(&mut app, "e"), Action::Edit);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn searches_until_escape() {
+        let (mut app, path) = app("tui-search", "- paint door @wip\n- fix roof @todo\n", "{}");
+        press(&mut app, "/roof");
+        assert_eq!(titles(&app), ["fix roof"]);
+        // Keys are typed into the search, not taken as commands
+        assert_eq!(press(&mut app, "q"), Action::Continue);
+        app.handle_key(KeyCode::Backspace);
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.search, "roof");
+        assert_eq!(titles(&app), ["fix roof"]);
+        press(&mut app, "/");
+        app.handle_key(KeyCode::Esc);
+        assert_eq!(app.search, "");
+        assert_eq!(titles(&app).len(), 2);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn changes_status_within_limits_unless_forced() {
+        let (mut app, path) = app(
+            "tui-status",
+            "- paint door @wip\n- fix roof @todo\n",
+            r#"{"wip": 1}"#,
+        );
+        press(&mut app, "jw");
+        assert_eq!(
+            app.message,
+            "2 @wip tasks, the limit is 1, press again to force"
+        );
+        assert_eq!(app.force, Some(TaskStatus::Wip));
+        // Any other key cancels the force
+        press(&mut app, "jj");
+        press(&mut app, "w");
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("fix roof @todo"));
+
+        press(&mut app, "w");
+        assert_eq!(app.message, "Set 'fix roof' to @wip");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "- paint door @wip\n- fix roof @wip\n"
+        );
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn defers_to_the_typed_date() {
+        let (mut app, path) = app("tui-defer", "- fix roof @todo\n", "{}");
+        press(&mut app, "f+3d");
+        assert!(matches!(&app.mode, Mode::Defer(input) if input == "+3d"));
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.message, "Deferred 'fix roof' until 2024-03-04");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "- fix roof @todo @s20240304\n"
+        );
+
+        press(&mut app, "fsoon");
+        app.handle_key(KeyCode::Enter);
+        assert!(matches!(app.mode, Mode::Normal));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "- fix roof @todo @s20240304\n"
+        );
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
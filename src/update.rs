@@ -0,0 +1,148 @@
+use crate::edit::{EditError, EditFile};
+use crate::journal;
+use crate::model::{Task, TaskStatus};
+use chrono::NaiveDate;
+use regex::Regex;
+use std::sync::LazyLock;
+
+static STATUS_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s*@(todo|wip|review|week|month|done)\b").unwrap());
+
+static CHECKED_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\s*[*-]\s+)\[[xX]\]").unwrap());
+
+static UNCHECKED_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\s*[*-]\s+)\[ \]").unwrap());
+
+static COMPLETED_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"@c[0-9]{8}").unwrap());
+
+static START_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s*@s[0-9]{8}").unwrap());
+
+static DUE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s*@d[0-9]{8}").unwrap());
+
+/// The task line with its status marker replaced. A task that is done gets a
+/// completion date and its box checked, and a checked item is unchecked when
+/// it is no longer done.
+pub fn with_status(line: &str, status: TaskStatus, today: NaiveDate) -> String {
+    let mut line = STATUS_RE.replace_all(line, "").to_string();
+    line = match status {
+        TaskStatus::Done => UNCHECKED_RE.replace(&line, "$1[x]").to_string(),
+        _ => CHECKED_RE.replace(&line, "$1[ ]").to_string(),
+    };
+    let mut line = line.trim_end().to_string();
+    if status != TaskStatus::NoStatus {
+        line.push_str(&format!(" {}", status));
+    }
+    if status == TaskStatus::Done && !COMPLETED_RE.is_match(&line) {
+        line.push_str(&format!(" @c{}", today.format("%Y%m%d")));
+    }
+    line
+}
+
+/// Rewrite a single task line in its project file
+fn update_line(
+    task: &Task,
+    command: &str,
+    update: impl FnOnce(&str) -> String,
+) -> Result<(), EditError> {
+    let source = task
+        .source
+        .as_ref()
+        .ok_or(EditError::NoSource(task.title()))?;
+    let mut file = EditFile::open(&source.path)?;
+    let i = file.task_line(task)?;
+    file.lines[i] = update(&file.lines[i]);
+    journal::commit(command, &mut [&mut file])
+}
+
+pub fn set_status(task: &Task, status: TaskStatus, today: NaiveDate) -> Result<(), EditError> {
+    let command = format!("set '{}' to {}", task.title(), status);
+    update_line(task, &command, |line| with_status(line, status, today))
+}
+
+/// The task line with its start date replaced by `date`
+pub fn with_start(line: &str, date: NaiveDate) -> String {
+    let line = START_RE.replace_all(line, "");
+    format!("{} @s{}", line.trim_end(), date.format("%Y%m%d"))
+}
+
+/// The task line with its due date replaced by `date`
+pub fn with_due(line: &str, date: NaiveDate) -> String {
+    let line = DUE_RE.replace_all(line, "");
+    format!("{} @d{}", line.trim_end(), date.format("%Y%m%d"))
+}
+
+/// Hide a task until `date` by giving it a start date
+pub fn defer(task: &Task, date: NaiveDate) -> Result<(), EditError> {
+    let command = format!("defer '{}' until {}", task.title(), date);
+    update_line(task, &command, |line| with_start(line, date))
+}
+
+/// The task line with a context marker for each of `add` that it does not
+/// have yet, and without the markers for `remove`
+pub fn with_contexts(line: &str, add: &[&str], remove: &[&str]) -> String {
+    let mut line = line.to_string();
+    for context in remove {
+        let marker = Regex::new(&format!(r"\s*#x{}\b", regex::escape(context))).unwrap();
+        line = marker.replace_all(&line, "").to_string();
+    }
+    let mut line = line.trim_end().to_string();
+    for context in add {
+        let marker = format!("#x{}", context);
+        if !line.split_whitespace().any(|w| w == marker) {
+            line.push_str(&format!(" {}", marker));
+        }
+    }
+    line
+}
+
+/// Add and remove contexts of a task
+pub fn tag(task: &Task, add: &[&str], remove: &[&str]) -> Result<(), EditError> {
+    let changes: Vec<String> = add
+        .iter()
+        .map(|c| format!("+{}", c))
+        .chain(remove.iter().map(|c| format!("-{}", c)))
+        .collect();
+    let command = format!("tag '{}' {}", task.title(), changes.join(" "));
+    update_line(task, &command, |line| with_contexts(line, add, remove))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()
+    }
+
+    #[test]
+    fn done_checks_the_box_and_other_statuses_uncheck_it() {
+        assert_eq!(
+            with_status("- [ ] paint door @wip", TaskStatus::Done, today()),
+            "- [x] paint door @done @c20240301"
+        );
+        assert_eq!(
+            with_status("  * [X] paint door @done", TaskStatus::Todo, today()),
+            "  * [ ] paint door @todo"
+        );
+        assert_eq!(
+            with_status("- paint door", TaskStatus::Done, today()),
+            "- paint door @done @c20240301"
+        );
+    }
+
+    #[test]
+    fn adds_and_removes_contexts() {
+        assert_eq!(
+            with_contexts(
+                "- call mom #xhome #xphone @todo",
+                &["phone", "car"],
+                &["home"]
+            ),
+            "- call mom #xphone @todo #xcar"
+        );
+        assert_eq!(
+            with_contexts("- call mom #xhomework", &[], &["home"]),
+            "- call mom #xhomework"
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use crate::listing::{GroupBy, SortKey};
+use crate::query::{context_marker, parse_status, Query};
+use serde::{Deserialize, Serialize};
+
+/// A named listing from the config, e.g.
+/// `"focus": {"status": "wip,todo", "context": "focus", "sort": "priority,due"}`.
+/// The fields take the same values as the command line options.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct View {
+    pub status: Option<String>,
+    pub not_status: Option<String>,
+    pub context: Option<String>,
+    pub not_context: Option<String>,
+    pub query: Option<String>,
+    pub sort: Option<String>,
+    pub group_by: Option<String>,
+}
+
+fn split<T>(list: &str, parse: impl Fn(&str) -> Result<T, String>) -> Result<Vec<T>, String> {
+    list.split(',').map(parse).collect()
+}
+
+fn contexts(list: &str) -> Result<Vec<String>, String> {
+    split(list, |c| Ok(context_marker(c)))
+}
+
+impl View {
+    /// Everything the view filters on as a single query
+    pub fn filter(&self) -> Result<Query, String> {
+        let mut query: Query = self
+            .query
+            .as_deref()
+            .unwrap_or("")
+            .parse()
+            .map_err(|e| format!("Invalid query: {}", e))?;
+        if let Some(status) = &self.status {
+            query = query.and(Query::Status(split(status, parse_status)?));
+        }
+        if let Some(status) = &self.not_status {
+            query = query.and(Query::Status(split(status, parse_status)?).negate());
+        }
+        if let Some(context) = &self.context {
+            query = query.and(Query::Context(contexts(context)?));
+        }
+        if let Some(context) = &self.not_context {
+            query = query.and(Query::Context(contexts(context)?).negate());
+        }
+        Ok(query)
+    }
+
+    pub fn sort_keys(&self) -> Result<Vec<SortKey>, String> {
+        match &self.sort {
+            Some(sort) => split(sort, str::parse),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn group_by(&self) -> Result<Option<GroupBy>, String> {
+        self.group_by.as_deref().map(str::parse).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Task;
+    use chrono::NaiveDate;
+
+    fn view(json: &str) -> View {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn matching(view: &View, tasks: &[Task]) -> Vec<String> {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let filter = view.filter().unwrap();
+        tasks
+            .iter()
+            .filter(|t| filter.matches(t, today))
+            .map(|t| t.title())
+            .collect()
+    }
+
+    #[test]
+    fn filters_on_every_field() {
+        let tasks = vec![
+            Task::from("- paint door @wip #xhome", "house.md"),
+            Task::from("- fix roof @todo #xhome #xfocus @d20240305", "house.md"),
+            Task::from("- call mom @todo #xphone", "family.md"),
+            Task::from("- mow lawn @review #xfocus", "garden.md"),
+        ];
+        assert_eq!(matching(&View::default(), &tasks).len(), 4);
+        assert_eq!(
+            matching(
+                &view(r#"{"status": "wip,todo", "context": "home"}"#),
+                &tasks
+            ),
+            ["paint door", "fix roof"]
+        );
+        assert_eq!(
+            matching(
+                &view(r#"{"not_status": "wip", "not_context": "focus"}"#),
+                &tasks
+            ),
+            ["call mom"]
+        );
+        assert_eq!(
+            matching(
+                &view(r##"{"query": "due<=+7d", "context": "#xfocus"}"##),
+                &tasks
+            ),
+            ["fix roof"]
+        );
+    }
+
+    #[test]
+    fn reports_bad_fields() {
+        assert!(view(r#"{"status": "wip,later"}"#).filter().is_err());
+        assert!(view(r#"{"query": "due<"}"#)
+            .filter()
+            .unwrap_err()
+            .starts_with("Invalid query"));
+        assert!(view(r#"{"sort": "due,size"}"#).sort_keys().is_err());
+        assert_eq!(
+            view(r#"{"group_by": "ctx"}"#).group_by(),
+            Ok(Some(GroupBy::Context))
+        );
+    }
+}